@@ -0,0 +1,74 @@
+use std::io::Write;
+
+use crate::builtins::Stdio;
+use crate::shell::Shell;
+
+pub const DEFAULTS: [(&str, &str); 2] = [("ls", "ls --color=auto"), ("grep", "grep --color=auto")];
+
+pub fn format_alias(name: &str, value: &str) -> String {
+    format!("alias {}='{}'", name, value.replace('\'', "'\\''"))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '$', '`', '=', '\'', '"', '\\', ' ', '\t'])
+}
+
+pub fn alias(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let args: Vec<_> = args.iter().filter(|arg| *arg != "-p").collect();
+
+    if args.is_empty() {
+        for (name, value) in &shell.aliases {
+            let _ = writeln!(stdio.stdout, "{}", format_alias(name, value));
+        }
+        return 0
+    }
+
+    let mut status = 0;
+
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) => {
+                if !is_valid_name(name) {
+                    let _ = writeln!(stdio.stderr, "shsh: alias: `{}': invalid alias name", name);
+                    status = 1;
+                    continue;
+                }
+                shell.aliases.insert(name.to_string(), value.to_string());
+            }
+            None => match shell.aliases.get(arg) {
+                Some(value) => {
+                    let _ = writeln!(stdio.stdout, "{}", format_alias(arg, value));
+                }
+                None => {
+                    let _ = writeln!(stdio.stderr, "shsh: alias: {}: not found", arg);
+                    status = 1;
+                }
+            },
+        }
+    }
+
+    status
+}
+
+pub fn unalias(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    if args.is_empty() {
+        let _ = writeln!(stdio.stderr, "unalias: usage: unalias [-a] name [name ...]");
+        return 2
+    }
+
+    let mut status = 0;
+
+    for arg in args {
+        if arg == "-a" {
+            shell.aliases.clear();
+            continue;
+        }
+
+        if shell.aliases.remove(arg).is_none() {
+            let _ = writeln!(stdio.stderr, "shsh: unalias: {}: not found", arg);
+            status = 1;
+        }
+    }
+
+    status
+}
@@ -0,0 +1,384 @@
+use crate::shell::Shell;
+
+const MAX_DEPTH: usize = 64;
+
+const OPERATORS: [&str; 39] = [
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=", "/=", "%=", "+=", "-=",
+    "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?", ":", "=", ",", "(", ")",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Assign(String, &'static str, Box<Expr>),
+    Increment(String, i64, bool),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+fn digit_value(c: char, base: u32) -> Option<u32> {
+    let value = match c {
+        '0'..='9' => c as u32 - '0' as u32,
+        'a'..='z' => c as u32 - 'a' as u32 + 10,
+        'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+        'A'..='Z' => c as u32 - 'A' as u32 + 36,
+        '@' => 62,
+        '_' => 63,
+        _ => return None,
+    };
+
+    (value < base).then_some(value)
+}
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    let (base, digits) = match text.split_once('#') {
+        Some((base, digits)) => match base.parse::<u32>() {
+            Ok(base) if (2..=64).contains(&base) => (base, digits),
+            _ => return Err(format!("invalid arithmetic base (error token is \"{}\")", text)),
+        },
+        None if text.starts_with("0x") || text.starts_with("0X") => (16, &text[2..]),
+        None if text.len() > 1 && text.starts_with('0') => (8, &text[1..]),
+        None => (10, text),
+    };
+
+    if digits.is_empty() {
+        return Err(format!("invalid number (error token is \"{}\")", text));
+    }
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        match digit_value(c, base) {
+            Some(digit) => value = value.wrapping_mul(base as i64).wrapping_add(digit as i64),
+            None => return Err(format!("value too great for base (error token is \"{}\")", text)),
+        }
+    }
+
+    Ok(value)
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = vec![];
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || matches!(chars[pos], '#' | '@' | '_')) {
+                pos += 1;
+            }
+            tokens.push(Token::Number(parse_number(&chars[start..pos].iter().collect::<String>())?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Name(chars[start..pos].iter().collect()));
+        } else {
+            let rest: String = chars[pos..].iter().collect();
+            match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    pos += op.len();
+                }
+                None => return Err(format!("syntax error: invalid arithmetic operator (error token is \"{}\")", rest)),
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn binary_precedence(op: &str) -> Option<u8> {
+    let precedence = match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | ">" | "<=" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        "**" => 11,
+        _ => return None,
+    };
+    Some(precedence)
+}
+
+fn is_assignment(op: &str) -> bool {
+    matches!(op, "=" | "*=" | "/=" | "%=" | "+=" | "-=" | "<<=" | ">>=" | "&=" | "^=" | "|=")
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn error(&self) -> String {
+        let rest: Vec<String> = self.tokens[self.pos..]
+            .iter()
+            .map(|token| match token {
+                Token::Number(n) => n.to_string(),
+                Token::Name(name) => name.clone(),
+                Token::Op(op) => op.to_string(),
+            })
+            .collect();
+
+        if rest.is_empty() {
+            "syntax error: operand expected".to_string()
+        } else {
+            format!("syntax error in expression (error token is \"{}\")", rest.join(" "))
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() != Some(op) {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn parse_comma(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_assignment()?;
+
+        while self.peek_op() == Some(",") {
+            self.pos += 1;
+            expr = Expr::Binary(",", Box::new(expr), Box::new(self.parse_assignment()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_assignment(&mut self) -> Result<Expr, String> {
+        if let (Some(Token::Name(name)), Some(Token::Op(op))) = (self.tokens.get(self.pos), self.tokens.get(self.pos + 1)) {
+            if is_assignment(op) {
+                let (name, op) = (name.clone(), *op);
+                self.pos += 2;
+                return Ok(Expr::Assign(name, op, Box::new(self.parse_assignment()?)));
+            }
+        }
+
+        self.parse_conditional()
+    }
+
+    fn parse_conditional(&mut self) -> Result<Expr, String> {
+        let condition = self.parse_binary(1)?;
+
+        if self.peek_op() != Some("?") {
+            return Ok(condition);
+        }
+        self.pos += 1;
+
+        let then = self.parse_assignment()?;
+        self.expect(":")?;
+        let otherwise = self.parse_assignment()?;
+
+        Ok(Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)))
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut left = self.parse_unary()?;
+
+        while let Some(op) = self.peek_op() {
+            let precedence = match binary_precedence(op) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            self.pos += 1;
+
+            let next = if op == "**" { precedence } else { precedence + 1 };
+            let right = self.parse_binary(next)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.peek_op() {
+            Some(op @ ("++" | "--")) => {
+                if let Some(Token::Name(name)) = self.tokens.get(self.pos + 1) {
+                    let name = name.clone();
+                    self.pos += 2;
+                    return Ok(Expr::Increment(name, if op == "++" { 1 } else { -1 }, true));
+                }
+
+                self.pos += 1;
+                let sign = if op == "++" { "+" } else { "-" };
+                let operand = Expr::Unary(sign, Box::new(self.parse_unary()?));
+                Ok(Expr::Unary(sign, Box::new(operand)))
+            }
+            Some(op @ ("-" | "+" | "!" | "~")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.peek().cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(Expr::Number(n))
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                match self.peek_op() {
+                    Some(op @ ("++" | "--")) => {
+                        self.pos += 1;
+                        Ok(Expr::Increment(name, if op == "++" { 1 } else { -1 }, false))
+                    }
+                    _ => Ok(Expr::Var(name)),
+                }
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let expr = self.parse_comma()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ => Err(self.error()),
+        }
+    }
+}
+
+fn variable(shell: &mut Shell, name: &str, depth: usize) -> Result<i64, String> {
+    let value = shell.get_var(name).unwrap_or_default();
+    let value = value.trim();
+
+    if value.is_empty() {
+        return Ok(0);
+    }
+    if let Ok(n) = value.parse::<i64>() {
+        return Ok(n);
+    }
+
+    evaluate_at(shell, value, depth + 1)
+}
+
+fn apply(op: &str, left: i64, right: i64) -> Result<i64, String> {
+    let value = match op {
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err("division by 0".to_string()),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "**" if right < 0 => return Err("exponent less than 0".to_string()),
+        "**" => left.wrapping_pow(right.min(u32::MAX as i64) as u32),
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "<" => (left < right) as i64,
+        ">" => (left > right) as i64,
+        "<=" => (left <= right) as i64,
+        ">=" => (left >= right) as i64,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "&" => left & right,
+        "^" => left ^ right,
+        "|" => left | right,
+        "," => right,
+        _ => return Err(format!("{}: unknown operator", op)),
+    };
+    Ok(value)
+}
+
+fn eval(shell: &mut Shell, expr: &Expr, depth: usize) -> Result<i64, String> {
+    match expr {
+        Expr::Number(n) => Ok(*n),
+        Expr::Var(name) => variable(shell, name, depth),
+        Expr::Unary(op, operand) => {
+            let value = eval(shell, operand, depth)?;
+            Ok(match *op {
+                "-" => value.wrapping_neg(),
+                "!" => (value == 0) as i64,
+                "~" => !value,
+                _ => value,
+            })
+        }
+        Expr::Binary("&&", left, right) => {
+            Ok((eval(shell, left, depth)? != 0 && eval(shell, right, depth)? != 0) as i64)
+        }
+        Expr::Binary("||", left, right) => {
+            Ok((eval(shell, left, depth)? != 0 || eval(shell, right, depth)? != 0) as i64)
+        }
+        Expr::Binary(op, left, right) => {
+            let left = eval(shell, left, depth)?;
+            let right = eval(shell, right, depth)?;
+            apply(op, left, right)
+        }
+        Expr::Assign(name, op, value) => {
+            let value = eval(shell, value, depth)?;
+            let value = match op.strip_suffix('=').filter(|op| !op.is_empty()) {
+                Some(op) => apply(op, variable(shell, name, depth)?, value)?,
+                None => value,
+            };
+            shell.set_var(name, &value.to_string())?;
+            Ok(value)
+        }
+        Expr::Increment(name, delta, prefix) => {
+            let old = variable(shell, name, depth)?;
+            let new = old.wrapping_add(*delta);
+            shell.set_var(name, &new.to_string())?;
+            Ok(if *prefix { new } else { old })
+        }
+        Expr::Conditional(condition, then, otherwise) => {
+            if eval(shell, condition, depth)? != 0 {
+                eval(shell, then, depth)
+            } else {
+                eval(shell, otherwise, depth)
+            }
+        }
+    }
+}
+
+fn evaluate_at(shell: &mut Shell, expr: &str, depth: usize) -> Result<i64, String> {
+    if depth > MAX_DEPTH {
+        return Err("expression recursion level exceeded".to_string());
+    }
+
+    let mut parser = Parser { tokens: tokenize(expr)?, pos: 0 };
+    if parser.peek().is_none() {
+        return Ok(0);
+    }
+
+    let parsed = parser.parse_comma()?;
+    if parser.peek().is_some() {
+        return Err(parser.error());
+    }
+
+    eval(shell, &parsed, depth)
+}
+
+pub fn evaluate(shell: &mut Shell, expr: &str) -> Result<i64, String> {
+    evaluate_at(shell, expr, 0).map_err(|err| format!("{}: {}", expr.trim(), err))
+}
@@ -0,0 +1,771 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::lexer::{self, Operator, ParseError, RedirectOp, Token, Word, WordPart};
+use crate::shell;
+
+pub const KEYWORDS: [&str; 17] = ["!", "{", "}", "case", "do", "done", "elif", "else", "esac", "fi", "for", "function", "if", "in", "then", "until", "while"];
+
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub fd: Option<i32>,
+    pub op: RedirectOp,
+    pub target: Word,
+}
+
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+    pub append: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseItem {
+    pub patterns: Vec<Word>,
+    pub body: List,
+    pub terminator: Operator,
+}
+
+#[derive(Debug, Clone)]
+pub enum Compound {
+    If { branches: Vec<(List, List)>, otherwise: Option<List> },
+    Loop { condition: List, body: List, until: bool },
+    For { name: String, words: Option<Vec<Word>>, body: List },
+    ArithFor { init: String, condition: String, update: String, body: List },
+    Arith(String),
+    Case { word: Word, items: Vec<CaseItem> },
+    Group(List),
+    Subshell(List),
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Function(String, List),
+    Compound(Compound, Vec<Redirect>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListItem {
+    pub and_or: AndOr,
+    pub background: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct List {
+    pub items: Vec<ListItem>,
+}
+
+impl List {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(fd) = self.fd {
+            write!(f, "{}", fd)?;
+        }
+
+        match self.op {
+            RedirectOp::DLess | RedirectOp::DLessDash => write!(f, "{}", self.op),
+            _ => write!(f, "{}{}", self.op, self.target),
+        }
+    }
+}
+
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = if self.append { "+=" } else { "=" };
+        write!(f, "{}{}{}", self.name, op, self.value)
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let assignments = self.assignments.iter().map(|assignment| assignment.to_string());
+        let words = self.words.iter().map(|word| word.to_string());
+        let redirects = self.redirects.iter().map(|redirect| redirect.to_string());
+
+        write!(f, "{}", assignments.chain(words).chain(redirects).collect::<Vec<_>>().join(" "))
+    }
+}
+
+impl fmt::Display for Compound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Compound::If { branches, otherwise } => {
+                for (i, (condition, body)) in branches.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { " elif" };
+                    write!(f, "{} {}; then {};", keyword, condition, body)?;
+                }
+                if let Some(body) = otherwise {
+                    write!(f, " else {};", body)?;
+                }
+                write!(f, " fi")
+            }
+            Compound::Loop { condition, body, until } => {
+                let keyword = if *until { "until" } else { "while" };
+                write!(f, "{} {}; do {}; done", keyword, condition, body)
+            }
+            Compound::For { name, words: Some(words), body } => {
+                let words: Vec<_> = words.iter().map(|word| word.to_string()).collect();
+                write!(f, "for {} in {}; do {}; done", name, words.join(" "), body)
+            }
+            Compound::For { name, words: None, body } => write!(f, "for {}; do {}; done", name, body),
+            Compound::ArithFor { init, condition, update, body } => {
+                write!(f, "for (({};{};{})); do {}; done", init, condition, update, body)
+            }
+            Compound::Arith(expr) => write!(f, "(({}))", expr),
+            Compound::Group(list) => write!(f, "{{ {}; }}", list),
+            Compound::Subshell(list) => write!(f, "( {} )", list),
+            Compound::Case { word, items } => {
+                write!(f, "case {} in", word)?;
+                for item in items {
+                    let patterns: Vec<_> = item.patterns.iter().map(|pattern| pattern.to_string()).collect();
+                    write!(f, " {}) {} {}", patterns.join("|"), item.body, item.terminator)?;
+                }
+                write!(f, " esac")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Simple(simple) => write!(f, "{}", simple),
+            Command::Function(name, body) => write!(f, "{}() {{ {}; }}", name, body),
+            Command::Compound(compound, redirects) => {
+                write!(f, "{}", compound)?;
+                for redirect in redirects {
+                    write!(f, " {}", redirect)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let commands: Vec<_> = self.commands.iter().map(|command| command.to_string()).collect();
+
+        if self.negated {
+            write!(f, "! ")?;
+        }
+        write!(f, "{}", commands.join(" | "))
+    }
+}
+
+impl fmt::Display for AndOr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+
+        for (connector, pipeline) in &self.rest {
+            match connector {
+                Connector::And => write!(f, " && {}", pipeline)?,
+                Connector::Or => write!(f, " || {}", pipeline)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{}", item.and_or)?;
+            if item.background {
+                write!(f, " &")?;
+            } else if i + 1 < self.items.len() {
+                write!(f, ";")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    aliases: &'a BTreeMap<String, String>,
+    active_aliases: Vec<(String, usize)>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<Token>, aliases: &'a BTreeMap<String, String>) -> Self {
+        Self { tokens, pos: 0, aliases, active_aliases: vec![] }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_operator(&self) -> Option<Operator> {
+        match self.peek() {
+            Some(Token::Operator(op)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError::Syntax(format!("syntax error near unexpected token `{}'", token)),
+            None => ParseError::Incomplete,
+        }
+    }
+
+    fn peek_keyword(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Word(word)) => match word.parts.as_slice() {
+                [WordPart::Literal(keyword)] => Some(keyword.as_str()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn at_end(&self, ends: &[&str]) -> bool {
+        match self.peek() {
+            Some(Token::Operator(op)) => ends.contains(&op.to_string().as_str()),
+            Some(Token::Word(_)) => self.peek_keyword().is_some_and(|keyword| ends.contains(&keyword)),
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.peek_keyword() != Some(keyword) {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek_operator() == Some(Operator::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_list(&mut self, ends: &[&str]) -> Result<List, ParseError> {
+        let mut list = List::default();
+
+        loop {
+            self.skip_newlines();
+
+            if self.peek().is_none() || self.at_end(ends) {
+                break;
+            }
+
+            let and_or = self.parse_and_or()?;
+            let mut background = false;
+
+            match self.peek_operator() {
+                Some(Operator::Semi) | Some(Operator::Newline) => self.pos += 1,
+                Some(Operator::Amp) => {
+                    background = true;
+                    self.pos += 1;
+                }
+                _ if self.at_end(ends) => (),
+                None if self.peek().is_none() => (),
+                _ => return Err(self.unexpected()),
+            }
+
+            list.items.push(ListItem { and_or, background });
+        }
+
+        Ok(list)
+    }
+
+    fn parse_and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.parse_pipeline()?;
+        let mut rest = vec![];
+
+        loop {
+            let connector = match self.peek_operator() {
+                Some(Operator::And) => Connector::And,
+                Some(Operator::Or) => Connector::Or,
+                _ => break,
+            };
+            self.pos += 1;
+            self.skip_newlines();
+
+            rest.push((connector, self.parse_pipeline()?));
+        }
+
+        Ok(AndOr { first, rest })
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut pipeline = Pipeline::default();
+
+        if self.peek_keyword() == Some("!") {
+            pipeline.negated = true;
+            self.pos += 1;
+        }
+        pipeline.commands.push(self.parse_command()?);
+
+        while self.peek_operator() == Some(Operator::Pipe) {
+            self.pos += 1;
+            self.skip_newlines();
+
+            pipeline.commands.push(self.parse_command()?);
+        }
+
+        Ok(pipeline)
+    }
+
+    fn parse_function_body(&mut self) -> Result<List, ParseError> {
+        self.skip_newlines();
+
+        if self.peek_operator() == Some(Operator::LParen) {
+            let first = Pipeline { commands: vec![self.parse_command()?], negated: false };
+            let and_or = AndOr { first, rest: vec![] };
+            return Ok(List { items: vec![ListItem { and_or, background: false }] });
+        }
+
+        self.expect_keyword("{")?;
+        let body = self.parse_list(&["}"])?;
+        self.expect_keyword("}")?;
+
+        if body.is_empty() {
+            return Err(ParseError::Syntax("syntax error near unexpected token `}'".to_string()));
+        }
+
+        Ok(body)
+    }
+
+    fn parse_function(&mut self) -> Result<Option<Command>, ParseError> {
+        let keyword = self.peek_keyword() == Some("function");
+        let offset = if keyword { 1 } else { 0 };
+
+        let name = match self.tokens.get(self.pos + offset) {
+            Some(Token::Word(word)) => match word.parts.as_slice() {
+                [WordPart::Literal(name)] if keyword || !KEYWORDS.contains(&name.as_str()) => name.clone(),
+                _ if keyword => return Err(ParseError::Syntax(format!("`{}': not a valid identifier", word))),
+                _ => return Ok(None),
+            },
+            _ if keyword => {
+                self.pos += 1;
+                return Err(self.unexpected());
+            }
+            _ => return Ok(None),
+        };
+
+        let parens = self.tokens.get(self.pos + offset + 1) == Some(&Token::Operator(Operator::LParen));
+        if !keyword && !parens {
+            return Ok(None);
+        }
+
+        self.pos += offset + 1;
+        if parens {
+            self.pos += 1;
+            if self.peek_operator() != Some(Operator::RParen) {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+        }
+
+        Ok(Some(Command::Function(name, self.parse_function_body()?)))
+    }
+
+    fn parse_compound_list(&mut self, ends: &[&str]) -> Result<List, ParseError> {
+        let list = self.parse_list(ends)?;
+
+        if list.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(list)
+    }
+
+    fn parse_do_group(&mut self) -> Result<List, ParseError> {
+        self.skip_newlines();
+        self.expect_keyword("do")?;
+        let body = self.parse_compound_list(&["done"])?;
+        self.expect_keyword("done")?;
+
+        Ok(body)
+    }
+
+    fn parse_if(&mut self) -> Result<Compound, ParseError> {
+        let mut branches = vec![];
+        let mut otherwise = None;
+
+        loop {
+            self.pos += 1;
+            let condition = self.parse_compound_list(&["then"])?;
+            self.expect_keyword("then")?;
+            let body = self.parse_compound_list(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+
+            match self.peek_keyword() {
+                Some("elif") => continue,
+                Some("else") => {
+                    self.pos += 1;
+                    otherwise = Some(self.parse_compound_list(&["fi"])?);
+                }
+                _ => (),
+            }
+
+            self.expect_keyword("fi")?;
+            return Ok(Compound::If { branches, otherwise });
+        }
+    }
+
+    fn parse_loop(&mut self) -> Result<Compound, ParseError> {
+        let until = self.peek_keyword() == Some("until");
+        self.pos += 1;
+
+        let condition = self.parse_compound_list(&["do"])?;
+        let body = self.parse_do_group()?;
+
+        Ok(Compound::Loop { condition, body, until })
+    }
+
+    fn parse_for(&mut self) -> Result<Compound, ParseError> {
+        self.pos += 1;
+
+        if let Some(Token::Arith(expr)) = self.peek() {
+            let parts: Vec<String> = expr.split(';').map(|part| part.to_string()).collect();
+            if parts.len() != 3 {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+
+            if self.peek_operator() == Some(Operator::Semi) {
+                self.pos += 1;
+            }
+            let body = self.parse_do_group()?;
+
+            let [init, condition, update] = parts.try_into().unwrap();
+            return Ok(Compound::ArithFor { init, condition, update, body });
+        }
+
+        let name = match self.peek_keyword() {
+            Some(name) if shell::is_valid_name(name) => name.to_string(),
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+        self.skip_newlines();
+
+        let mut words = None;
+        if self.peek_keyword() == Some("in") {
+            self.pos += 1;
+
+            let mut list = vec![];
+            while let Some(Token::Word(word)) = self.peek() {
+                list.push(word.clone());
+                self.pos += 1;
+            }
+            words = Some(list);
+
+            match self.peek_operator() {
+                Some(Operator::Semi) | Some(Operator::Newline) => self.pos += 1,
+                _ => return Err(self.unexpected()),
+            }
+        } else if self.peek_operator() == Some(Operator::Semi) {
+            self.pos += 1;
+        }
+
+        let body = self.parse_do_group()?;
+        Ok(Compound::For { name, words, body })
+    }
+
+    fn parse_case(&mut self) -> Result<Compound, ParseError> {
+        self.pos += 1;
+
+        let word = match self.peek() {
+            Some(Token::Word(word)) => word.clone(),
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+        self.skip_newlines();
+        self.expect_keyword("in")?;
+
+        let mut items = vec![];
+
+        loop {
+            self.skip_newlines();
+            if self.peek_keyword() == Some("esac") {
+                self.pos += 1;
+                return Ok(Compound::Case { word, items });
+            }
+
+            if self.peek_operator() == Some(Operator::LParen) {
+                self.pos += 1;
+            }
+
+            let mut patterns = vec![];
+            loop {
+                match self.peek() {
+                    Some(Token::Word(pattern)) => patterns.push(pattern.clone()),
+                    _ => return Err(self.unexpected()),
+                }
+                self.pos += 1;
+
+                if self.peek_operator() != Some(Operator::Pipe) {
+                    break;
+                }
+                self.pos += 1;
+            }
+
+            if self.peek_operator() != Some(Operator::RParen) {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+
+            let body = self.parse_list(&["esac", ";;", ";&", ";;&"])?;
+            let terminator = match self.peek_operator() {
+                Some(op @ (Operator::DSemi | Operator::SemiAmp | Operator::DSemiAmp)) => {
+                    self.pos += 1;
+                    op
+                }
+                _ => Operator::DSemi,
+            };
+
+            items.push(CaseItem { patterns, body, terminator });
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Compound, ParseError> {
+        self.pos += 1;
+
+        let list = self.parse_compound_list(&["}"])?;
+        self.expect_keyword("}")?;
+
+        Ok(Compound::Group(list))
+    }
+
+    fn parse_subshell(&mut self) -> Result<Compound, ParseError> {
+        self.pos += 1;
+
+        let list = self.parse_compound_list(&[")"])?;
+        if self.peek_operator() != Some(Operator::RParen) {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+
+        Ok(Compound::Subshell(list))
+    }
+
+    fn parse_command(&mut self) -> Result<Command, ParseError> {
+        if let Some(function) = self.parse_function()? {
+            return Ok(function);
+        }
+
+        let compound = match self.peek_keyword() {
+            Some("if") => Some(self.parse_if()?),
+            Some("while" | "until") => Some(self.parse_loop()?),
+            Some("for") => Some(self.parse_for()?),
+            Some("case") => Some(self.parse_case()?),
+            Some("{") => Some(self.parse_group()?),
+            Some("then" | "elif" | "else" | "fi" | "do" | "done" | "esac" | "}") => return Err(self.unexpected()),
+            _ => match self.peek() {
+                Some(Token::Arith(expr)) => {
+                    let expr = expr.clone();
+                    self.pos += 1;
+                    Some(Compound::Arith(expr))
+                }
+                Some(Token::Operator(Operator::LParen)) => Some(self.parse_subshell()?),
+                _ => None,
+            },
+        };
+
+        if let Some(compound) = compound {
+            let mut redirects = vec![];
+            while let Some(Token::Redirect(..)) = self.peek() {
+                redirects.push(self.parse_redirect()?);
+            }
+            return Ok(Command::Compound(compound, redirects));
+        }
+
+        self.parse_simple_command().map(Command::Simple)
+    }
+
+    fn parse_redirect(&mut self) -> Result<Redirect, ParseError> {
+        let (fd, op) = match self.peek() {
+            Some(Token::Redirect(fd, op)) => (*fd, *op),
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let target = word.clone();
+                self.pos += 1;
+                Ok(Redirect { fd, op, target })
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn expand_aliases(&mut self, checks: &mut Vec<usize>) -> Result<(), ParseError> {
+
+        while let Some(Token::Word(word)) = self.peek() {
+            let name = match word.parts.as_slice() {
+                [WordPart::Literal(name)] => name.clone(),
+                _ => break,
+            };
+
+            let pos = self.pos;
+            self.active_aliases.retain(|(_, end)| *end > pos);
+            if self.active_aliases.iter().any(|(active, _)| *active == name) {
+                break;
+            }
+
+            let value = match self.aliases.get(&name) {
+                Some(value) => value,
+                None => break,
+            };
+            let tokens = lexer::tokenize(value).map_err(|_| ParseError::Syntax(format!("{}: bad alias value", name)))?;
+            let count = tokens.len();
+
+            for (_, end) in &mut self.active_aliases {
+                *end = *end + count - 1;
+            }
+            for at in checks.iter_mut().filter(|at| **at > pos) {
+                *at = *at + count - 1;
+            }
+            if value.ends_with([' ', '\t']) {
+                checks.push(pos + count);
+            }
+
+            self.tokens.splice(pos..pos + 1, tokens);
+            self.active_aliases.push((name, pos + count));
+        }
+
+        Ok(())
+    }
+
+    fn parse_simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand::default();
+        let mut checks = vec![self.pos];
+
+        loop {
+            if checks.contains(&self.pos) {
+                checks.retain(|at| *at != self.pos);
+                self.expand_aliases(&mut checks)?;
+            }
+
+            match self.peek() {
+                Some(Token::Word(word)) => {
+                    match parse_assignment(word).filter(|_| command.words.is_empty()) {
+                        Some(assignment) => {
+                            command.assignments.push(assignment);
+                            checks.push(self.pos + 1);
+                        }
+                        None => command.words.push(word.clone()),
+                    }
+                    self.pos += 1;
+                }
+                Some(Token::Redirect(..)) => command.redirects.push(self.parse_redirect()?),
+                _ => break,
+            }
+        }
+
+        if command.assignments.is_empty() && command.words.is_empty() && command.redirects.is_empty() {
+            return Err(self.unexpected());
+        }
+
+        Ok(command)
+    }
+}
+
+fn parse_assignment(word: &Word) -> Option<Assignment> {
+    let first = match word.parts.first() {
+        Some(WordPart::Literal(first)) => first,
+        _ => return None,
+    };
+
+    let (name, rest) = first.split_once('=')?;
+    let (name, append) = match name.strip_suffix('+') {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    if !shell::is_valid_name(name) {
+        return None;
+    }
+
+    let mut parts = vec![];
+    if !rest.is_empty() {
+        parts.push(WordPart::Literal(rest.to_string()));
+    }
+    parts.extend(word.parts[1..].iter().cloned());
+
+    Some(Assignment { name: name.to_string(), value: Word { parts }, append })
+}
+
+pub fn parse(input: &str, aliases: &BTreeMap<String, String>) -> Result<List, ParseError> {
+    parse_tokens(lexer::tokenize(input)?, aliases)
+}
+
+pub fn parse_tokens(tokens: Vec<Token>, aliases: &BTreeMap<String, String>) -> Result<List, ParseError> {
+    let mut parser = Parser::new(tokens, aliases);
+
+    let list = parser.parse_list(&[])?;
+    if parser.peek().is_some() {
+        return Err(parser.unexpected());
+    }
+
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parses(input: &str) -> bool {
+        parse(input, &BTreeMap::new()).is_ok()
+    }
+
+    #[test]
+    fn subshell_in_compound_positions() {
+        assert!(parses("if (true); then (echo y); elif (false); then :; else (echo n); fi"));
+        assert!(parses("while (false); do (echo x); done"));
+        assert!(parses("until (true); do :; done"));
+        assert!(parses("for i in a; do (echo $i); done"));
+        assert!(parses("case a in a) (echo a);; esac"));
+        assert!(parses("{ (echo inner); }"));
+        assert!(parses("! (false)"));
+        assert!(parses("true && (echo a) || (echo b)"));
+    }
+
+    #[test]
+    fn reserved_words_are_not_function_names() {
+        assert!(!parses("if () { :; }"));
+        assert!(parses("function if { :; }"));
+        assert!(parses("f() (echo sub)"));
+    }
+}
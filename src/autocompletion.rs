@@ -1,7 +1,9 @@
 use std::fs::{self, metadata};
 use std::os::unix::fs::PermissionsExt;
+use std::ffi::CStr;
 
 use crate::executor;
+use crate::expand;
 
 pub struct Completion {
     cmds: Vec<String>,
@@ -55,7 +57,70 @@ impl Completion {
         completions
     }
 
+    fn get_users(prefix: &str) -> Vec<String> {
+        let mut users = vec![];
+
+        unsafe {
+            libc::setpwent();
+            loop {
+                let entry = libc::getpwent();
+                if entry.is_null() {
+                    break;
+                }
+
+                let name = CStr::from_ptr((*entry).pw_name).to_string_lossy().into_owned();
+                if name.starts_with(prefix) {
+                    users.push(format!("~{}/", name));
+                }
+            }
+            libc::endpwent();
+        }
+
+        users.sort();
+        users.dedup();
+        users
+    }
+
+    fn get_tilde_paths(&self, path: &str) -> (Vec<String>, usize, bool) {
+        let (user, rest) = match path[1..].split_once('/') {
+            Some(split) => split,
+            None => return (Self::get_users(&path[1..]), path.len(), false),
+        };
+
+        let home = match user {
+            "" => Some(executor::get_env("HOME".to_string())).filter(|home| !home.is_empty()).or_else(|| expand::passwd_home(None)),
+            user => expand::passwd_home(Some(user)),
+        };
+        let home = match home {
+            Some(home) => home,
+            None => return (vec![], 0, false),
+        };
+
+        let expanded = format!("{}/{}", home, rest);
+        if metadata(&expanded).is_ok_and(|md| md.is_dir()) {
+            return (self.get_dir(&expanded), 0, true);
+        }
+
+        let (dir, name) = match expanded.rsplit_once('/') {
+            Some(split) => split,
+            None => return (vec![], 0, false),
+        };
+        let typed = &path[..path.len() - name.len()];
+
+        let entries = self
+            .find_path_completion(self.get_dir(&dir.to_string()), &name.to_string())
+            .into_iter()
+            .map(|entry| format!("{}{}", typed, entry))
+            .collect();
+
+        (entries, path.len(), false)
+    }
+
     pub fn get_paths(&self, dir: &String) -> (Vec<String>, usize, bool)  {        
+        if dir.starts_with('~') {
+            return self.get_tilde_paths(dir);
+        }
+
         match metadata(dir) {
             Ok(md) => {
                 if md.is_dir() {
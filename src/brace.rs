@@ -0,0 +1,153 @@
+use crate::lexer::{Word, WordPart};
+
+#[derive(Clone)]
+enum Item {
+    Char(char),
+    Part(WordPart),
+}
+
+fn items(word: &Word) -> Vec<Item> {
+    word.parts
+        .iter()
+        .flat_map(|part| match part {
+            WordPart::Literal(s) => s.chars().map(Item::Char).collect(),
+            part => vec![Item::Part(part.clone())],
+        })
+        .collect()
+}
+
+fn to_word(items: Vec<Item>) -> Word {
+    let mut word = Word::default();
+    let mut literal = String::new();
+
+    for item in items {
+        match item {
+            Item::Char(c) => literal.push(c),
+            Item::Part(part) => {
+                if !literal.is_empty() {
+                    word.parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                }
+                word.parts.push(part);
+            }
+        }
+    }
+
+    if !literal.is_empty() {
+        word.parts.push(WordPart::Literal(literal));
+    }
+
+    word
+}
+
+fn find_close(items: &[Item], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = vec![];
+
+    for (i, item) in items.iter().enumerate().skip(open) {
+        match item {
+            Item::Char('{') => depth += 1,
+            Item::Char('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((i, commas));
+                }
+            }
+            Item::Char(',') if depth == 1 => commas.push(i),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+fn range(start: i64, end: i64, step: i64) -> Vec<i64> {
+    let ascending = start <= end;
+    let mut values = vec![];
+    let mut next = Some(start);
+
+    while let Some(value) = next.filter(|value| if ascending { *value <= end } else { *value >= end }) {
+        values.push(value);
+        next = if ascending { value.checked_add(step) } else { value.checked_sub(step) };
+    }
+
+    values
+}
+
+fn is_padded(bound: &str) -> bool {
+    let digits = bound.strip_prefix('-').unwrap_or(bound);
+    digits.len() > 1 && digits.starts_with('0')
+}
+
+fn sequence(items: &[Item]) -> Option<Vec<String>> {
+    let text = items
+        .iter()
+        .map(|item| match item {
+            Item::Char(c) => Some(*c),
+            Item::Part(_) => None,
+        })
+        .collect::<Option<String>>()?;
+
+    let (start, end, step) = match text.split("..").collect::<Vec<_>>().as_slice() {
+        [start, end] => (start.to_string(), end.to_string(), 1),
+        [start, end, step] => (start.to_string(), end.to_string(), step.parse::<i64>().ok()?),
+        _ => return None,
+    };
+    let step = step.saturating_abs().max(1);
+
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let width = match is_padded(&start) || is_padded(&end) {
+            true => start.len().max(end.len()),
+            false => 0,
+        };
+        return Some(range(first, last, step).into_iter().map(|value| format!("{:0width$}", value, width = width)).collect());
+    }
+
+    match (start.as_bytes(), end.as_bytes()) {
+        ([first], [last]) if first.is_ascii_alphabetic() && last.is_ascii_alphabetic() => Some(
+            range(*first as i64, *last as i64, step)
+                .into_iter()
+                .map(|value| (value as u8 as char).to_string())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn expand(items: Vec<Item>) -> Vec<Vec<Item>> {
+    for open in 0..items.len() {
+        if !matches!(items[open], Item::Char('{')) {
+            continue;
+        }
+
+        let (close, commas) = match find_close(&items, open) {
+            Some(found) => found,
+            None => continue,
+        };
+
+        let alternatives: Vec<Vec<Item>> = if commas.is_empty() {
+            match sequence(&items[open + 1..close]) {
+                Some(values) => values.into_iter().map(|value| value.chars().map(Item::Char).collect()).collect(),
+                None => continue,
+            }
+        } else {
+            let bounds: Vec<usize> = std::iter::once(open).chain(commas).chain(std::iter::once(close)).collect();
+            bounds.windows(2).map(|bounds| items[bounds[0] + 1..bounds[1]].to_vec()).collect()
+        };
+
+        let mut expanded = vec![];
+        for alternative in alternatives {
+            let mut combined = items[..open].to_vec();
+            combined.extend(alternative);
+            combined.extend_from_slice(&items[close + 1..]);
+
+            expanded.extend(expand(combined));
+        }
+        return expanded;
+    }
+
+    vec![items]
+}
+
+pub fn expand_braces(word: &Word) -> Vec<Word> {
+    expand(items(word)).into_iter().map(to_word).collect()
+}
@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::exit;
+use std::rc::Rc;
+
+use crate::alias;
+use crate::ast;
+use crate::dirs;
+use crate::executor;
+use crate::functions;
+use crate::jobs;
+use crate::script;
+use crate::shell::Shell;
+use crate::vars;
+
+pub struct Stdio {
+    pub stdout: ManuallyDrop<File>,
+    pub stderr: ManuallyDrop<File>,
+}
+
+impl Stdio {
+    pub fn new() -> Self {
+        let _ = io::stdout().flush();
+        let (stdout, stderr) = unsafe { (File::from_raw_fd(1), File::from_raw_fd(2)) };
+
+        Self { stdout: ManuallyDrop::new(stdout), stderr: ManuallyDrop::new(stderr) }
+    }
+}
+
+pub trait Builtin {
+    fn run(&self, shell: &mut Shell, argv: &[String], stdio: &mut Stdio) -> i32;
+}
+
+type BuiltinFn = fn(&mut Shell, &[String], &mut Stdio) -> i32;
+
+impl Builtin for BuiltinFn {
+    fn run(&self, shell: &mut Shell, argv: &[String], stdio: &mut Stdio) -> i32 {
+        self(shell, argv, stdio)
+    }
+}
+
+struct Entry {
+    builtin: Rc<dyn Builtin>,
+    enabled: bool,
+}
+
+pub struct Registry {
+    entries: BTreeMap<&'static str, Entry>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        let mut registry = Self { entries: BTreeMap::new() };
+
+        registry.add(".", |shell, argv, stdio| script::source(shell, &argv[1..], stdio));
+        registry.add("alias", |shell, argv, stdio| alias::alias(shell, &argv[1..], stdio));
+        registry.add("bg", |shell, argv, stdio| jobs::bg(shell, &argv[1..], stdio));
+        registry.add("break", |shell, argv, stdio| executor::loop_control(shell, &argv[0], &argv[1..], stdio));
+        registry.add("builtin", builtin);
+        registry.add("cd", |shell, argv, stdio| dirs::cd(shell, &argv[1..], stdio));
+        registry.add("command", command);
+        registry.add("continue", |shell, argv, stdio| executor::loop_control(shell, &argv[0], &argv[1..], stdio));
+        registry.add("declare", |shell, argv, stdio| vars::declare(shell, &argv[0], &argv[1..], stdio));
+        registry.add("dirs", |shell, argv, stdio| dirs::dirs(shell, &argv[1..], stdio));
+        registry.add("disown", |shell, argv, stdio| jobs::disown(shell, &argv[1..], stdio));
+        registry.add("enable", enable);
+        registry.add("exec", exec);
+        registry.add("exit", exit_shell);
+        registry.add("export", |shell, argv, stdio| vars::export(shell, &argv[1..], stdio));
+        registry.add("fg", |shell, argv, stdio| jobs::fg(shell, &argv[1..], stdio));
+        registry.add("jobs", |shell, argv, stdio| jobs::jobs(shell, &argv[1..], stdio));
+        registry.add("local", |shell, argv, stdio| vars::local(shell, &argv[1..], stdio));
+        registry.add("popd", |shell, argv, stdio| dirs::popd(shell, &argv[1..], stdio));
+        registry.add("pushd", |shell, argv, stdio| dirs::pushd(shell, &argv[1..], stdio));
+        registry.add("pwd", |shell, argv, stdio| dirs::pwd(shell, &argv[1..], stdio));
+        registry.add("readonly", |shell, argv, stdio| vars::readonly(shell, &argv[1..], stdio));
+        registry.add("return", |shell, argv, stdio| functions::ret(shell, &argv[1..], stdio));
+        registry.add("set", |shell, argv, stdio| executor::set_options(shell, &argv[1..], stdio));
+        registry.add("shift", |shell, argv, stdio| executor::shift(shell, &argv[1..], stdio));
+        registry.add("shopt", |shell, argv, stdio| executor::shopt(shell, &argv[1..], stdio));
+        registry.add("source", |shell, argv, stdio| script::source(shell, &argv[1..], stdio));
+        registry.add("type", type_builtin);
+        registry.add("typeset", |shell, argv, stdio| vars::declare(shell, &argv[0], &argv[1..], stdio));
+        registry.add("unalias", |shell, argv, stdio| alias::unalias(shell, &argv[1..], stdio));
+        registry.add("unset", |shell, argv, stdio| vars::unset(shell, &argv[1..], stdio));
+        registry.add("wait", |shell, argv, stdio| jobs::wait(shell, &argv[1..], stdio));
+
+        registry
+    }
+
+    fn add(&mut self, name: &'static str, run: BuiltinFn) {
+        self.entries.insert(name, Entry { builtin: Rc::new(run), enabled: true });
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Builtin>> {
+        self.entries.get(name).filter(|entry| entry.enabled).map(|entry| entry.builtin.clone())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.get(name).is_some_and(|entry| entry.enabled)
+    }
+}
+
+pub fn run(shell: &mut Shell, builtin: &dyn Builtin, argv: &[String]) -> i32 {
+    let mut stdio = Stdio::new();
+    builtin.run(shell, argv, &mut stdio)
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata().is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+fn find_in_path(shell: &Shell, name: &str) -> Vec<String> {
+    if name.contains('/') {
+        return match is_executable(Path::new(name)) {
+            true => vec![name.to_string()],
+            false => vec![],
+        };
+    }
+
+    shell
+        .get_var("PATH")
+        .unwrap_or_default()
+        .split(':')
+        .map(|dir| format!("{}/{}", if dir.is_empty() { "." } else { dir }, name))
+        .filter(|path| is_executable(Path::new(path)))
+        .collect()
+}
+
+enum Kind {
+    Alias(String),
+    Keyword,
+    Function,
+    Builtin,
+    File(String),
+}
+
+fn resolve(shell: &Shell, name: &str, all: bool, aliases: bool) -> Vec<Kind> {
+    let mut kinds = vec![];
+
+    if let Some(value) = shell.aliases.get(name).filter(|_| aliases) {
+        kinds.push(Kind::Alias(value.clone()));
+    }
+    if ast::KEYWORDS.contains(&name) {
+        kinds.push(Kind::Keyword);
+    }
+    if shell.functions.contains_key(name) {
+        kinds.push(Kind::Function);
+    }
+    if shell.builtins.contains(name) {
+        kinds.push(Kind::Builtin);
+    }
+    if all || kinds.is_empty() {
+        kinds.extend(find_in_path(shell, name).into_iter().map(Kind::File));
+    }
+
+    if !all {
+        kinds.truncate(1);
+    }
+    kinds
+}
+
+fn describe(shell: &Shell, name: &str, kind: &Kind) -> String {
+    match kind {
+        Kind::Alias(value) => format!("{} is aliased to `{}'", name, value),
+        Kind::Keyword => format!("{} is a shell keyword", name),
+        Kind::Function => match shell.functions.get(name) {
+            Some(body) => format!("{} is a function\n{} () {{\n{}\n}}", name, name, body),
+            None => format!("{} is a function", name),
+        },
+        Kind::Builtin => format!("{} is a shell builtin", name),
+        Kind::File(path) => format!("{} is {}", name, path),
+    }
+}
+
+fn type_builtin(shell: &mut Shell, argv: &[String], stdio: &mut Stdio) -> i32 {
+    let mut all = false;
+    let mut kind_only = false;
+    let mut path_only = false;
+    let mut names = vec![];
+
+    for arg in &argv[1..] {
+        if !names.is_empty() || !arg.starts_with('-') || arg.len() < 2 {
+            names.push(arg);
+            continue;
+        }
+
+        for flag in arg[1..].chars() {
+            match flag {
+                'a' => all = true,
+                't' => kind_only = true,
+                'p' | 'P' => path_only = true,
+                _ => {
+                    let _ = writeln!(stdio.stderr, "shsh: type: -{}: invalid option", flag);
+                    let _ = writeln!(stdio.stderr, "type: usage: type [-apt] name [name ...]");
+                    return 2
+                }
+            }
+        }
+    }
+
+    let mut status = 0;
+
+    for name in names {
+        let kinds = resolve(shell, name, all, !path_only);
+        if kinds.is_empty() {
+            if !kind_only && !path_only {
+                let _ = writeln!(stdio.stderr, "shsh: type: {}: not found", name);
+            }
+            status = 1;
+            continue;
+        }
+
+        for kind in kinds {
+            let line = match (&kind, kind_only, path_only) {
+                (Kind::File(path), _, true) => path.clone(),
+                (_, _, true) => continue,
+                (Kind::Alias(_), true, _) => "alias".to_string(),
+                (Kind::Keyword, true, _) => "keyword".to_string(),
+                (Kind::Function, true, _) => "function".to_string(),
+                (Kind::Builtin, true, _) => "builtin".to_string(),
+                (Kind::File(_), true, _) => "file".to_string(),
+                _ => describe(shell, name, &kind),
+            };
+            let _ = writeln!(stdio.stdout, "{}", line);
+        }
+    }
+
+    status
+}
+
+fn command(shell: &mut Shell, argv: &[String], stdio: &mut Stdio) -> i32 {
+    let verbose = match argv.get(1).map(|arg| arg.as_str()) {
+        None => return 0,
+        Some("-v") => false,
+        Some("-V") => true,
+        Some(arg) => {
+            let _ = writeln!(stdio.stderr, "shsh: command: {}: invalid option", arg);
+            let _ = writeln!(stdio.stderr, "command: usage: command [-vV] command [arg ...]");
+            return 2
+        }
+    };
+
+    let mut status = 0;
+
+    for name in &argv[2..] {
+        let kind = match resolve(shell, name, false, true).pop() {
+            Some(kind) => kind,
+            None => {
+                if verbose {
+                    let _ = writeln!(stdio.stderr, "shsh: command: {}: not found", name);
+                }
+                status = 1;
+                continue;
+            }
+        };
+
+        let line = match (&kind, verbose) {
+            (_, true) => describe(shell, name, &kind),
+            (Kind::Alias(value), false) => alias::format_alias(name, value),
+            (Kind::File(path), false) => path.clone(),
+            (_, false) => name.clone(),
+        };
+        let _ = writeln!(stdio.stdout, "{}", line);
+    }
+
+    status
+}
+
+fn builtin(shell: &mut Shell, argv: &[String], stdio: &mut Stdio) -> i32 {
+    let name = match argv.get(1) {
+        Some(name) => name,
+        None => return 0,
+    };
+
+    match shell.builtins.get(name) {
+        Some(builtin) => builtin.run(shell, &argv[1..], stdio),
+        None => {
+            let _ = writeln!(stdio.stderr, "shsh: builtin: {}: not a shell builtin", name);
+            1
+        }
+    }
+}
+
+fn enable(shell: &mut Shell, argv: &[String], stdio: &mut Stdio) -> i32 {
+    let mut disable = false;
+    let mut all = false;
+    let mut names = vec![];
+
+    for arg in &argv[1..] {
+        match arg.as_str() {
+            "-n" if names.is_empty() => disable = true,
+            "-a" if names.is_empty() => all = true,
+            "-p" if names.is_empty() => (),
+            _ if arg.starts_with('-') && names.is_empty() => {
+                let _ = writeln!(stdio.stderr, "shsh: enable: {}: invalid option", arg);
+                let _ = writeln!(stdio.stderr, "enable: usage: enable [-a] [-n] [-p] [name ...]");
+                return 2
+            }
+            _ => names.push(arg),
+        }
+    }
+
+    if names.is_empty() {
+        for (name, entry) in &shell.builtins.entries {
+            if all || entry.enabled != disable {
+                let flag = if entry.enabled { "" } else { "-n " };
+                let _ = writeln!(stdio.stdout, "enable {}{}", flag, name);
+            }
+        }
+        return 0
+    }
+
+    let mut status = 0;
+
+    for name in names {
+        match shell.builtins.entries.get_mut(name.as_str()) {
+            Some(entry) => entry.enabled = !disable,
+            None => {
+                let _ = writeln!(stdio.stderr, "shsh: enable: {}: not a shell builtin", name);
+                status = 1;
+            }
+        }
+    }
+
+    status
+}
+
+fn exec(shell: &mut Shell, argv: &[String], stdio: &mut Stdio) -> i32 {
+    if let Some(name) = argv.get(1) {
+        let _ = writeln!(stdio.stderr, "shsh: exec: {}: replacing the shell is not supported", name);
+        return 2
+    }
+
+    shell.keep_redirects = true;
+    0
+}
+
+fn exit_shell(shell: &mut Shell, argv: &[String], stdio: &mut Stdio) -> i32 {
+    match argv.get(1).map(|arg| arg.parse::<i32>()) {
+        None => exit(shell.last_status),
+        Some(Ok(code)) => exit(code),
+        Some(Err(_)) => {
+            let _ = writeln!(stdio.stderr, "shsh: exit: {}: numeric argument required", argv[1]);
+            exit(2)
+        }
+    }
+}
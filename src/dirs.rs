@@ -0,0 +1,349 @@
+use std::env;
+use std::io::Write;
+use std::path::Path;
+
+use crate::builtins::Stdio;
+use crate::redirect;
+use crate::shell::Shell;
+
+pub fn init(shell: &mut Shell) {
+    let cwd = match env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(_) => return,
+    };
+
+    let inherited = shell.vars.get("PWD").filter(|pwd| pwd.starts_with('/') && Path::new(pwd).canonicalize().is_ok_and(|pwd| pwd == cwd));
+    if inherited.is_none() {
+        let _ = shell.set_var("PWD", &cwd.display().to_string());
+    }
+}
+
+fn current(shell: &Shell) -> String {
+    match shell.vars.get("PWD") {
+        Some(pwd) if pwd.starts_with('/') => pwd.to_string(),
+        _ => env::current_dir().map(|cwd| cwd.display().to_string()).unwrap_or_default(),
+    }
+}
+
+fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = vec![];
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+
+    format!("/{}", components.join("/"))
+}
+
+fn change_dir(shell: &mut Shell, dir: &str, physical: bool) -> Result<(), String> {
+    let old = current(shell);
+    let target = match dir.starts_with('/') {
+        true => dir.to_string(),
+        false => format!("{}/{}", old, dir),
+    };
+    let target = if physical { target } else { normalize(&target) };
+
+    env::set_current_dir(&target).map_err(|err| format!("{}: {}", dir, redirect::describe_error(&err)))?;
+
+    let pwd = match physical {
+        true => env::current_dir().map(|cwd| cwd.display().to_string()).unwrap_or(target),
+        false => target,
+    };
+    shell.set_var("OLDPWD", &old)?;
+    shell.set_var("PWD", &pwd)
+}
+
+fn search_cdpath(shell: &Shell, dir: &str) -> Option<String> {
+    let relative = dir == "." || dir == ".." || dir.starts_with('/') || dir.starts_with("./") || dir.starts_with("../");
+    if relative {
+        return None;
+    }
+
+    let cdpath = shell.vars.get("CDPATH")?;
+    cdpath.split(':').filter(|entry| !entry.is_empty()).find_map(|entry| {
+        let candidate = format!("{}/{}", entry.trim_end_matches('/'), dir);
+        Path::new(&candidate).is_dir().then_some(candidate)
+    })
+}
+
+fn abbreviate(shell: &Shell, dir: &str) -> String {
+    match shell.vars.get("HOME").filter(|home| !home.is_empty() && *home != "/") {
+        Some(home) if dir == home => "~".to_string(),
+        Some(home) if dir.starts_with(&format!("{}/", home)) => format!("~{}", &dir[home.len()..]),
+        _ => dir.to_string(),
+    }
+}
+
+pub fn cd(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut physical = false;
+    let mut operands = vec![];
+
+    for (i, arg) in args.iter().enumerate() {
+        match arg.as_str() {
+            "--" => {
+                operands.extend(&args[i + 1..]);
+                break;
+            }
+            "-L" if operands.is_empty() => physical = false,
+            "-P" if operands.is_empty() => physical = true,
+            _ if arg.starts_with('-') && arg.len() > 1 && operands.is_empty() => {
+                let _ = writeln!(stdio.stderr, "shsh: cd: {}: invalid option", arg);
+                let _ = writeln!(stdio.stderr, "cd: usage: cd [-L|-P] [dir]");
+                return 2
+            }
+            _ => operands.push(arg),
+        }
+    }
+
+    let (dir, print) = match operands.as_slice() {
+        [] => match shell.vars.get("HOME") {
+            Some(home) if !home.is_empty() => (home.to_string(), false),
+            _ => {
+                let _ = writeln!(stdio.stderr, "shsh: cd: HOME not set");
+                return 1
+            }
+        },
+        [dir] if *dir == "-" => match shell.vars.get("OLDPWD") {
+            Some(oldpwd) => (oldpwd.to_string(), true),
+            None => {
+                let _ = writeln!(stdio.stderr, "shsh: cd: OLDPWD not set");
+                return 1
+            }
+        },
+        [dir] => match search_cdpath(shell, dir) {
+            Some(found) => (found, true),
+            None => (dir.to_string(), false),
+        },
+        _ => {
+            let _ = writeln!(stdio.stderr, "shsh: cd: too many arguments");
+            return 1
+        }
+    };
+
+    if let Err(err) = change_dir(shell, &dir, physical) {
+        let _ = writeln!(stdio.stderr, "shsh: cd: {}", err);
+        return 1
+    }
+
+    if print {
+        let _ = writeln!(stdio.stdout, "{}", current(shell));
+    }
+    0
+}
+
+pub fn pwd(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut physical = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                let _ = writeln!(stdio.stderr, "shsh: pwd: {}: invalid option", arg);
+                let _ = writeln!(stdio.stderr, "pwd: usage: pwd [-LP]");
+                return 2
+            }
+            _ => break,
+        }
+    }
+
+    let dir = match physical {
+        true => env::current_dir().map(|cwd| cwd.display().to_string()),
+        false => Ok(current(shell)),
+    };
+
+    match dir {
+        Ok(dir) => {
+            let _ = writeln!(stdio.stdout, "{}", dir);
+            0
+        }
+        Err(err) => {
+            let _ = writeln!(stdio.stderr, "shsh: pwd: {}", redirect::describe_error(&err));
+            1
+        }
+    }
+}
+
+fn stack(shell: &Shell) -> Vec<String> {
+    std::iter::once(current(shell)).chain(shell.dirstack.iter().cloned()).collect()
+}
+
+fn stack_index(shell: &Shell, builtin: &str, arg: &str, stdio: &mut Stdio) -> Result<Option<usize>, ()> {
+    let (from_end, digits) = match arg.split_at_checked(1) {
+        Some(("+", digits)) => (false, digits),
+        Some(("-", digits)) => (true, digits),
+        _ => return Ok(None),
+    };
+    if digits.is_empty() {
+        return Ok(None);
+    }
+
+    let n = match digits.parse::<usize>() {
+        Ok(n) => n,
+        Err(_) => {
+            let _ = writeln!(stdio.stderr, "shsh: {}: {}: invalid number", builtin, arg);
+            return Err(())
+        }
+    };
+
+    let len = shell.dirstack.len() + 1;
+    let index = match from_end {
+        true => len.checked_sub(n + 1),
+        false => Some(n).filter(|n| *n < len),
+    };
+
+    match index {
+        Some(index) => Ok(Some(index)),
+        None => {
+            let _ = writeln!(stdio.stderr, "shsh: {}: {}: directory stack index out of range", builtin, arg);
+            Err(())
+        }
+    }
+}
+
+fn print_stack(shell: &Shell, long: bool, per_line: bool, numbered: bool, stdio: &mut Stdio) {
+    let entries: Vec<String> = stack(shell)
+        .iter()
+        .map(|dir| if long { dir.clone() } else { abbreviate(shell, dir) })
+        .collect();
+
+    if numbered {
+        for (i, dir) in entries.iter().enumerate() {
+            let _ = writeln!(stdio.stdout, "{:2}  {}", i, dir);
+        }
+    } else if per_line {
+        for dir in entries {
+            let _ = writeln!(stdio.stdout, "{}", dir);
+        }
+    } else {
+        let _ = writeln!(stdio.stdout, "{}", entries.join(" "));
+    }
+}
+
+fn rotate_to(shell: &mut Shell, builtin: &str, entries: Vec<String>, stdio: &mut Stdio) -> i32 {
+    let (first, rest) = match entries.split_first() {
+        Some(split) => split,
+        None => return 0,
+    };
+
+    if let Err(err) = change_dir(shell, first, false) {
+        let _ = writeln!(stdio.stderr, "shsh: {}: {}", builtin, err);
+        return 1
+    }
+
+    shell.dirstack = rest.to_vec();
+    print_stack(shell, false, false, false, stdio);
+    0
+}
+
+pub fn pushd(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    match args {
+        [] => {
+            if shell.dirstack.is_empty() {
+                let _ = writeln!(stdio.stderr, "shsh: pushd: no other directory");
+                return 1
+            }
+
+            let mut entries = stack(shell);
+            entries.swap(0, 1);
+            rotate_to(shell, "pushd", entries, stdio)
+        }
+        [arg] => match stack_index(shell, "pushd", arg, stdio) {
+            Err(()) => 1,
+            Ok(Some(index)) => {
+                let mut entries = stack(shell);
+                entries.rotate_left(index);
+                rotate_to(shell, "pushd", entries, stdio)
+            }
+            Ok(None) => {
+                let old = current(shell);
+                let dir = search_cdpath(shell, arg).unwrap_or_else(|| arg.clone());
+                if let Err(err) = change_dir(shell, &dir, false) {
+                    let _ = writeln!(stdio.stderr, "shsh: pushd: {}", err);
+                    return 1
+                }
+
+                shell.dirstack.insert(0, old);
+                print_stack(shell, false, false, false, stdio);
+                0
+            }
+        },
+        _ => {
+            let _ = writeln!(stdio.stderr, "shsh: pushd: too many arguments");
+            1
+        }
+    }
+}
+
+pub fn popd(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    if shell.dirstack.is_empty() {
+        let _ = writeln!(stdio.stderr, "shsh: popd: directory stack empty");
+        return 1
+    }
+
+    let index = match args {
+        [] => 0,
+        [arg] => match stack_index(shell, "popd", arg, stdio) {
+            Ok(Some(index)) => index,
+            Ok(None) => {
+                let _ = writeln!(stdio.stderr, "shsh: popd: {}: invalid argument", arg);
+                return 2
+            }
+            Err(()) => return 1,
+        },
+        _ => {
+            let _ = writeln!(stdio.stderr, "shsh: popd: too many arguments");
+            return 1
+        }
+    };
+
+    if index > 0 {
+        shell.dirstack.remove(index - 1);
+        print_stack(shell, false, false, false, stdio);
+        return 0
+    }
+
+    let mut entries = stack(shell);
+    entries.remove(0);
+    rotate_to(shell, "popd", entries, stdio)
+}
+
+pub fn dirs(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut long = false;
+    let mut per_line = false;
+    let mut numbered = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "-c" => {
+                shell.dirstack.clear();
+                return 0
+            }
+            "-l" => long = true,
+            "-p" => per_line = true,
+            "-v" => numbered = true,
+            _ => match stack_index(shell, "dirs", arg, stdio) {
+                Ok(Some(index)) => {
+                    let dir = stack(shell).swap_remove(index);
+                    let _ = writeln!(stdio.stdout, "{}", if long { dir } else { abbreviate(shell, &dir) });
+                    return 0
+                }
+                Ok(None) => {
+                    let _ = writeln!(stdio.stderr, "shsh: dirs: {}: invalid option", arg);
+                    let _ = writeln!(stdio.stderr, "dirs: usage: dirs [-clpv] [+N] [-N]");
+                    return 2
+                }
+                Err(()) => return 1,
+            },
+        }
+    }
+
+    print_stack(shell, long, per_line, numbered, stdio);
+    0
+}
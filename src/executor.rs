@@ -1,135 +1,1023 @@
-use std::process::{Command, exit, Stdio, Child};
+use std::process::{self, exit};
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
 use std::collections::VecDeque;
-use std::path::Path;
-use std::fs::File;
+use std::rc::Rc;
+use std::io::{self, ErrorKind, Read, Write};
 use std::env;
+use std::fs;
 
-use regex::Regex;
-use glob::glob;
+use crate::ast;
+use crate::builtins::{self, Stdio};
+use crate::expand::{self, ExpandError};
+use crate::functions;
+use crate::jobs::{self, JobTable, ProcessState};
+use crate::lexer::{Operator, Word};
+use crate::redirect::{self, SavedFds};
+use crate::shell::{Flow, Options, Shell, Shopt};
+use crate::vars::{self, Variable};
 
-use crate::parser;
+pub struct Command {
+    pub assignments: Vec<(String, String)>,
+    pub name: String,
+    pub args: Vec<String>,
+    pub redirects: Vec<ast::Redirect>,
+}
+
+impl Command {
+    pub fn new() -> Self {
+        let assignments = vec![];
+        let name = String::new();
+        let args: Vec<String> = vec![];
+        let redirects = vec![];
+
+        Self { assignments, name, args, redirects }
+    }
+
+    pub fn argv(&self) -> Vec<String> {
+        std::iter::once(self.name.clone()).chain(self.args.iter().cloned()).collect()
+    }
+}
 
 pub fn get_env(name: String) -> String {
-    match env::var(name) {
-        Ok(value) => return value,
-        Err(_) => return " ".to_string(),
+    env::var(name).unwrap_or_default()
+}
+
+fn expand_command(shell: &mut Shell, simple: &ast::SimpleCommand) -> Result<Command, ExpandError> {
+    let mut command = Command::new();
+    shell.subst_status = None;
+
+    for assignment in &simple.assignments {
+        let mut value = expand::expand_assignment(shell, &assignment.value)?;
+        if assignment.append {
+            value = shell.get_var(&assignment.name).unwrap_or_default() + &value;
+        }
+        command.assignments.push((assignment.name.clone(), value));
     }
+
+    let mut words = expand::expand_words(shell, &simple.words)?.into_iter();
+    command.name = words.next().unwrap_or_default();
+    command.args = words.collect();
+
+    command.redirects = simple.redirects.clone();
+
+    Ok(command)
+}
+
+fn quote_trace(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_alphanumeric() || "-_./=:,+@%^".contains(c)) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
 }
 
-pub fn exec_command(mut cmds: VecDeque<parser::Command>) {
-    let mut prev_cmd = None;
+fn trace(shell: &Shell, cmd: &Command) {
+    let prefix = shell.get_var("PS4").unwrap_or_else(|| "+ ".to_string());
+
+    for (name, value) in &cmd.assignments {
+        eprintln!("{}{}={}", prefix, name, quote_trace(value));
+    }
+
+    if cmd.name.is_empty() && cmd.args.is_empty() {
+        return;
+    }
+
+    let words: Vec<_> = std::iter::once(&cmd.name).chain(&cmd.args).map(|arg| quote_trace(arg)).collect();
+    eprintln!("{}{}", prefix, words.join(" "));
+}
+
+fn assign(shell: &mut Shell, assignments: &[(String, String)]) -> i32 {
+    for (name, value) in assignments {
+        if let Err(err) = shell.set_var(name, value) {
+            eprintln!("shsh: {}", err);
+            return 1
+        }
+    }
+
+    0
+}
+
+fn push_assignments(shell: &mut Shell, assignments: &[(String, String)]) -> Result<Vec<(String, Option<Variable>)>, String> {
+    let mut saved = vec![];
+
+    for (name, value) in assignments {
+        saved.push((name.clone(), shell.vars.lookup(name).cloned()));
+
+        if let Err(err) = shell.set_var(name, value) {
+            pop_assignments(shell, saved);
+            return Err(err);
+        }
+        shell.vars.declare(name).exported = true;
+    }
+
+    Ok(saved)
+}
+
+fn pop_assignments(shell: &mut Shell, saved: Vec<(String, Option<Variable>)>) {
+    for (name, var) in saved.into_iter().rev() {
+        shell.vars.restore(&name, var);
+    }
+}
+
+pub fn exec_list(shell: &mut Shell, list: &ast::List) -> i32 {
+    let mut status = 0;
+
+    for item in &list.items {
+        status = if item.background {
+            exec_background(shell, &item.and_or)
+        } else {
+            exec_and_or(shell, &item.and_or)
+        };
 
-    while let Some(mut cmd) = cmds.pop_front() {
-        match cmd.name.as_str() {
-            "cd" => {
-                let mut path = get_env("HOME".to_string());
-                if path == "" {
-                    path = "/".to_string();
+        if shell.flow.is_some() {
+            break;
+        }
+    }
+
+    status
+}
+
+fn foreground_tty(shell: &Shell, background: bool) -> Option<RawFd> {
+    match &shell.terminal {
+        Some(terminal) if !background => Some(terminal.fd()),
+        _ => None,
+    }
+}
+
+fn fork_child(shell: &mut Shell, pgid: Option<i32>, background: bool, close_fds: &[RawFd], run: impl FnOnce(&mut Shell) -> i32) -> io::Result<i32> {
+    let _ = io::stdout().flush();
+    let tty = foreground_tty(shell, background);
+
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            match pgid {
+                Some(pgid) => jobs::reset_child(pgid, tty),
+                None if shell.terminal.is_some() => jobs::reset_signals(),
+                None => (),
+            }
+            for fd in close_fds {
+                unsafe { libc::close(*fd) };
+            }
+
+            match background {
+                true => shell.jobs = JobTable::new(),
+                false => shell.jobs.inherit(),
+            }
+            shell.terminal = None;
+            shell.interactive = false;
+
+            let status = run(shell);
+            let _ = io::stdout().flush();
+            unsafe { libc::_exit(status) }
+        }
+        pid => {
+            if let Some(pgid) = pgid {
+                let pgid = if pgid == 0 { pid } else { pgid };
+                unsafe { libc::setpgid(pid, pgid) };
+
+                if let Some(terminal) = shell.terminal.as_ref().filter(|_| tty.is_some()) {
+                    terminal.give_to(pgid, None);
                 }
+            }
+            Ok(pid)
+        }
+    }
+}
 
-                match cmd.args.len() {
-                    0 => (),
-                    1 => {
-                        let v = cmd.args.pop().unwrap();
-                        if v != "~" {
-                            path = v;
-                        }       
-                    }
-                    _ => {
-                        println!("To many directions provided; see cd man");
-                        return
-                    }
-                };
+pub fn capture_output(shell: &mut Shell, source: &str) -> Result<String, String> {
+    let list = ast::parse(source, &shell.aliases).map_err(|err| err.to_string())?;
+    let (read, write) = redirect::pipe().map_err(|err| format!("cannot make pipe: {}", err))?;
+
+    let child = fork_child(shell, None, false, &[read.as_raw_fd()], |shell| {
+        if unsafe { libc::dup2(write.as_raw_fd(), 1) } == -1 {
+            return 1
+        }
+        exec_list(shell, &list)
+    });
+    drop(write);
+    let pid = child.map_err(|err| format!("fork: {}", err))?;
+
+    let mut output = vec![];
+    let result = fs::File::from(read).read_to_end(&mut output);
+
+    let status = match jobs::wait_pid(pid, 0) {
+        Some(ProcessState::Exited(status)) => status,
+        _ => 0,
+    };
+    shell.last_status = status;
+    shell.subst_status = Some(status);
+
+    result.map_err(|err| format!("command substitution: {}", err))?;
+    Ok(String::from_utf8_lossy(&output).trim_end_matches('\n').to_string())
+}
+
+pub fn process_subst(shell: &mut Shell, output: bool, source: &str) -> Result<String, String> {
+    let list = ast::parse(source, &shell.aliases).map_err(|err| err.to_string())?;
+    let (read, write) = redirect::pipe().map_err(|err| format!("cannot make pipe: {}", err))?;
+    let (parent, child, target) = match output {
+        true => (write, read, 0),
+        false => (read, write, 1),
+    };
+
+    let mut close_fds: Vec<RawFd> = shell.proc_substs.iter().map(|(fd, _)| fd.as_raw_fd()).collect();
+    close_fds.push(parent.as_raw_fd());
 
-                let path = Path::new(&path);
+    let pid = fork_child(shell, None, false, &close_fds, |shell| {
+        if unsafe { libc::dup2(child.as_raw_fd(), target) } == -1 {
+            return 1
+        }
+        exec_list(shell, &list)
+    })
+    .map_err(|err| format!("fork: {}", err))?;
+    drop(child);
+
+    unsafe { libc::fcntl(parent.as_raw_fd(), libc::F_SETFD, 0) };
+    let path = format!("/dev/fd/{}", parent.as_raw_fd());
+    shell.proc_substs.push((parent, pid));
+
+    Ok(path)
+}
+
+fn close_proc_substs(shell: &mut Shell, mark: usize) {
+    let pids: Vec<i32> = shell.proc_substs.drain(mark..).map(|(_, pid)| pid).collect();
+    shell.subst_children.extend(pids);
+    shell.subst_children.retain(|pid| jobs::wait_pid(*pid, libc::WNOHANG).is_none());
+}
+
+fn add_job(shell: &mut Shell, pids: Vec<i32>, command: String) {
+    let pgid = match pids.first() {
+        Some(pgid) => *pgid,
+        None => return,
+    };
+
+    let job = shell.jobs.add(pgid, pids, command);
+    let (id, pid) = (job.id, job.last_pid());
 
-                if let Err(_) = env::set_current_dir(&path) {
-                    println!("Problem with changing directory; cannot find {:?} dir", path);
+    shell.last_background = Some(pid);
+    if shell.interactive {
+        println!("[{}] {}", id, pid);
+    }
+}
+
+fn exec_background(shell: &mut Shell, and_or: &ast::AndOr) -> i32 {
+    let is_simple = and_or.rest.is_empty()
+        && and_or.first.commands.iter().all(|command| matches!(command, ast::Command::Simple(_)));
+
+    if is_simple {
+        return exec_pipeline(shell, &and_or.first, true);
+    }
+
+    match fork_child(shell, Some(0), true, &[], |shell| exec_and_or(shell, and_or)) {
+        Ok(pid) => {
+            add_job(shell, vec![pid], and_or.to_string());
+            shell.set_status(vec![0]);
+            0
+        }
+        Err(err) => {
+            eprintln!("shsh: fork: {}", err);
+            shell.set_status(vec![1]);
+            1
+        }
+    }
+}
+
+fn exec_and_or(shell: &mut Shell, and_or: &ast::AndOr) -> i32 {
+    let mut status = exec_pipeline(shell, &and_or.first, false);
+    let mut errexit = and_or.rest.is_empty() && !and_or.first.negated;
+
+    for (i, (connector, pipeline)) in and_or.rest.iter().enumerate() {
+        if shell.flow.is_some() {
+            return status;
+        }
+
+        let run = match connector {
+            ast::Connector::And => status == 0,
+            ast::Connector::Or => status != 0,
+        };
+
+        if run {
+            status = exec_pipeline(shell, pipeline, false);
+            errexit = i + 1 == and_or.rest.len() && !pipeline.negated;
+        }
+    }
+
+    if status != 0 && errexit && shell.options.errexit && shell.condition_depth == 0 && shell.flow.is_none() {
+        exit(status);
+    }
+
+    status
+}
+
+fn expansion_error(shell: &mut Shell, err: ExpandError) -> i32 {
+    eprintln!("shsh: {}", err);
+    if matches!(err, ExpandError::Fatal(_)) && !shell.interactive {
+        exit(1);
+    }
+    1
+}
+
+fn exec_pipeline(shell: &mut Shell, pipeline: &ast::Pipeline, background: bool) -> i32 {
+    let status = run_pipeline(shell, pipeline, background);
+    if !pipeline.negated {
+        return status;
+    }
+
+    shell.last_status = if status == 0 { 1 } else { 0 };
+    shell.last_status
+}
+
+fn run_pipeline(shell: &mut Shell, pipeline: &ast::Pipeline, background: bool) -> i32 {
+    let mark = shell.proc_substs.len();
+    let status = run_stages(shell, pipeline, background);
+    close_proc_substs(shell, mark);
+    status
+}
+
+fn run_stages(shell: &mut Shell, pipeline: &ast::Pipeline, background: bool) -> i32 {
+    let mut steps = VecDeque::new();
+    let lone = pipeline.commands.len() == 1;
+
+    for command in &pipeline.commands {
+        let step = match command {
+            ast::Command::Simple(simple) => match expand_command(shell, simple) {
+                Ok(cmd) => Step::Simple(cmd),
+                Err(err) => {
+                    let status = expansion_error(shell, err);
+                    shell.set_status(vec![status]);
+                    return status
                 }
+            },
+            ast::Command::Function(name, body) if lone => {
+                shell.functions.insert(name.clone(), Rc::new(body.clone()));
+                shell.set_status(vec![0]);
+                return 0
+            }
+            ast::Command::Compound(compound, redirects) if lone && !matches!(compound, ast::Compound::Subshell(_)) => {
+                let status = exec_compound(shell, compound, redirects);
+                shell.set_status(vec![status]);
+                return status;
+            }
+            ast::Command::Function(name, body) => Step::Function(name, body),
+            ast::Command::Compound(compound, redirects) => Step::Compound(compound, redirects),
+        };
+        steps.push_back(step);
+    }
+
+    let (stages, pgid) = exec_command(shell, steps, background);
+
+    if background {
+        let pids = stages
+            .iter()
+            .filter_map(|stage| match stage {
+                Stage::Running(pid) => Some(*pid),
+                Stage::Done(_) => None,
+            })
+            .collect();
+        add_job(shell, pids, pipeline.to_string());
+        shell.set_status(vec![0]);
+        return 0;
+    }
+
+    let pipestatus = wait_stages(shell, stages, pgid, pipeline.to_string());
+    shell.set_status(pipestatus);
+
+    shell.last_status
+}
+
+fn exec_condition(shell: &mut Shell, list: &ast::List) -> i32 {
+    shell.condition_depth += 1;
+    let status = exec_list(shell, list);
+    shell.condition_depth -= 1;
+
+    status
+}
+
+fn leave_loop(shell: &mut Shell) -> bool {
+    match shell.flow {
+        Some(Flow::Break(count)) => {
+            shell.flow = if count > 1 { Some(Flow::Break(count - 1)) } else { None };
+            true
+        }
+        Some(Flow::Continue(count)) if count > 1 => {
+            shell.flow = Some(Flow::Continue(count - 1));
+            true
+        }
+        Some(Flow::Continue(_)) => {
+            shell.flow = None;
+            false
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
 
-                return
+fn exec_if(shell: &mut Shell, branches: &[(ast::List, ast::List)], otherwise: &Option<ast::List>) -> i32 {
+    for (condition, body) in branches {
+        let status = exec_condition(shell, condition);
+        if shell.flow.is_some() {
+            return status;
+        }
+
+        if status == 0 {
+            return exec_list(shell, body);
+        }
+    }
+
+    match otherwise {
+        Some(body) => exec_list(shell, body),
+        None => 0,
+    }
+}
+
+fn exec_loop(shell: &mut Shell, condition: &ast::List, body: &ast::List, until: bool) -> i32 {
+    let mut status = 0;
+
+    loop {
+        let result = exec_condition(shell, condition);
+        if shell.flow.is_some() {
+            if leave_loop(shell) {
+                break;
             }
-            "ls" => cmd.args.push("--color=auto".to_string()),
-            "grep" => cmd.args.push("--color=auto".to_string()),
-            "export" => {
-                let pattern = Regex::new("[A-Za-z0-9]+=[A-Za-z0-9]+").unwrap();
-                for arg in cmd.args {
-                    if pattern.is_match(&arg) {
-                        let args: Vec<_> = arg.split('=').collect();
+            continue;
+        }
 
-                        env::set_var(args[0], args[1]);
-                    }else {
-                        println!("Wrong command usage");
-                        return
+        if (result == 0) == until {
+            break;
+        }
+
+        status = exec_list(shell, body);
+        if leave_loop(shell) {
+            break;
+        }
+    }
+
+    status
+}
+
+fn exec_for(shell: &mut Shell, name: &str, words: &Option<Vec<Word>>, body: &ast::List) -> i32 {
+    let words = match words {
+        Some(words) => match expand::expand_words(shell, words) {
+            Ok(words) => words,
+            Err(err) => return expansion_error(shell, err),
+        },
+        None => shell.positional.clone(),
+    };
+
+    let mut status = 0;
+
+    for word in words {
+        if let Err(err) = shell.set_var(name, &word) {
+            eprintln!("shsh: {}", err);
+            return 1
+        }
+
+        status = exec_list(shell, body);
+        if leave_loop(shell) {
+            break;
+        }
+    }
+
+    status
+}
+
+fn exec_arith_for(shell: &mut Shell, init: &str, condition: &str, update: &str, body: &ast::List) -> i32 {
+    let mut status = 0;
+
+    if let Err(err) = expand::evaluate(shell, init) {
+        eprintln!("shsh: ((: {}", err);
+        return 1
+    }
+
+    loop {
+        if !condition.trim().is_empty() {
+            match expand::evaluate(shell, condition) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(err) => {
+                    eprintln!("shsh: ((: {}", err);
+                    return 1
+                }
+            }
+        }
+
+        status = exec_list(shell, body);
+        if leave_loop(shell) {
+            break;
+        }
+
+        if let Err(err) = expand::evaluate(shell, update) {
+            eprintln!("shsh: ((: {}", err);
+            return 1
+        }
+    }
+
+    status
+}
+
+fn exec_arith(shell: &mut Shell, expr: &str) -> i32 {
+    match expand::evaluate(shell, expr) {
+        Ok(0) => 1,
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("shsh: ((: {}", err);
+            1
+        }
+    }
+}
+
+fn exec_case(shell: &mut Shell, word: &Word, items: &[ast::CaseItem]) -> i32 {
+    let value = match expand::expand_word(shell, word) {
+        Ok(value) => value,
+        Err(err) => return expansion_error(shell, err.into()),
+    };
+
+    let mut status = 0;
+    let mut fallthrough = false;
+
+    for item in items {
+        if !fallthrough {
+            let mut matched = false;
+
+            for pattern in &item.patterns {
+                match expand::expand_pattern(shell, pattern) {
+                    Ok(pattern) if expand::matches_pattern(&pattern, &value) => {
+                        matched = true;
+                        break;
                     }
+                    Ok(_) => (),
+                    Err(err) => return expansion_error(shell, err.into()),
                 }
-                return
             }
-            "" => return,
-            "exit" => exit(0),
-            _ => (),
+
+            if !matched {
+                continue;
+            }
+        }
+
+        status = exec_list(shell, &item.body);
+        if shell.flow.is_some() {
+            break;
+        }
+
+        match item.terminator {
+            Operator::SemiAmp => fallthrough = true,
+            Operator::DSemiAmp => fallthrough = false,
+            _ => break,
+        }
+    }
+
+    status
+}
+
+fn exec_compound(shell: &mut Shell, compound: &ast::Compound, redirects: &[ast::Redirect]) -> i32 {
+    let mut fds = SavedFds::new();
+
+    if let Err(err) = fds.apply(shell, redirects) {
+        eprintln!("shsh: {}", err);
+        fds.restore();
+        return 1
+    }
+
+    let is_loop = matches!(compound, ast::Compound::Loop { .. } | ast::Compound::For { .. } | ast::Compound::ArithFor { .. });
+    if is_loop {
+        shell.loop_depth += 1;
+    }
+
+    let status = match compound {
+        ast::Compound::If { branches, otherwise } => exec_if(shell, branches, otherwise),
+        ast::Compound::Loop { condition, body, until } => exec_loop(shell, condition, body, *until),
+        ast::Compound::For { name, words, body } => exec_for(shell, name, words, body),
+        ast::Compound::ArithFor { init, condition, update, body } => exec_arith_for(shell, init, condition, update, body),
+        ast::Compound::Case { word, items } => exec_case(shell, word, items),
+        ast::Compound::Arith(expr) => exec_arith(shell, expr),
+        ast::Compound::Group(list) | ast::Compound::Subshell(list) => exec_list(shell, list),
+    };
+
+    if is_loop {
+        shell.loop_depth -= 1;
+    }
+    fds.restore();
+
+    status
+}
+
+pub fn set_options(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    if args.is_empty() {
+        vars::print_all(shell, stdio);
+        return 0
+    }
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            shell.positional = args.cloned().collect();
+            break;
         }
 
-        let stdin = prev_cmd.map_or(Stdio::inherit(), |out: Child| Stdio::from(out.stdout.unwrap()));
+        let enable = arg.starts_with('-');
+        if !enable && !arg.starts_with('+') || arg.len() < 2 {
+            shell.positional = std::iter::once(arg).chain(args).cloned().collect();
+            break;
+        }
 
-        let mut stdout = Stdio::inherit();
-        if !cmds.is_empty() {
-            stdout = Stdio::piped();
+        if &arg[1..] != "o" {
+            for flag in arg[1..].chars() {
+                match Options::from_flag(flag).and_then(|name| shell.options.get_mut(name)) {
+                    Some(option) => *option = enable,
+                    None => {
+                        let _ = writeln!(stdio.stderr, "shsh: set: {}{}: invalid option", &arg[..1], flag);
+                        return 2
+                    }
+                }
+            }
+            continue;
         }
 
-        if !cmd.redirect_file.is_empty() {
-            match File::create(cmd.redirect_file) {
-                Ok(file) => {
-                    stdout = Stdio::from(file);
+        let name = match args.next() {
+            Some(name) => name,
+            None => {
+                for name in Options::NAMES {
+                    if enable {
+                        let _ = writeln!(stdio.stdout, "{:<15} {}", name, if shell.options.get(name) == Some(true) { "on" } else { "off" });
+                    } else {
+                        let flag = if shell.options.get(name) == Some(true) { "-o" } else { "+o" };
+                        let _ = writeln!(stdio.stdout, "set {} {}", flag, name);
+                    }
                 }
-                Err(_) => println!("Problem with redirecting to file"),
+                return 0
+            }
+        };
+
+        match shell.options.get_mut(name) {
+            Some(option) => *option = enable,
+            None => {
+                let _ = writeln!(stdio.stderr, "shsh: set: {}: invalid option name", name);
+                return 2
             }
         }
+    }
+
+    0
+}
+
+pub fn shopt(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut enable = None;
+    let mut print = false;
+    let mut quiet = false;
+    let mut names = vec![];
+
+    for arg in args {
+        match arg.as_str() {
+            "-s" if names.is_empty() => enable = Some(true),
+            "-u" if names.is_empty() => enable = Some(false),
+            "-p" if names.is_empty() => print = true,
+            "-q" if names.is_empty() => quiet = true,
+            _ if arg.starts_with('-') && names.is_empty() => {
+                let _ = writeln!(stdio.stderr, "shsh: shopt: {}: invalid option", arg);
+                let _ = writeln!(stdio.stderr, "shopt: usage: shopt [-pqsu] [optname ...]");
+                return 2
+            }
+            _ => names.push(arg.as_str()),
+        }
+    }
+
+    let listing = names.is_empty();
+    if listing {
+        names = Shopt::NAMES.to_vec();
+        if let Some(enable) = enable {
+            names.retain(|name| shell.shopt.get(name) == Some(enable));
+        }
+        enable = None;
+    }
+
+    let mut status = 0;
 
-        let mut parsed_args: Vec<String> = vec![];
-        for arg in &cmd.args {
-            if arg == "~" {
-                let path = get_env("HOME".to_string());
-                parsed_args.push(path);
+    for name in names {
+        let value = match shell.shopt.get_mut(name) {
+            Some(value) => value,
+            None => {
+                let _ = writeln!(stdio.stderr, "shsh: shopt: {}: invalid shell option name", name);
+                status = 1;
                 continue;
             }
-            
-            if !arg.contains("*") || arg.contains(" ") {
-                parsed_args.push(arg.to_string());
-                continue;            
-            }
-            
-            match glob(&arg) {
-                Ok(paths) => {
-                    for entry in paths {
-                        match entry {
-                            Ok(path) => parsed_args.push(path.display().to_string()),
-                            Err(path) => {
-                                println!("Problem with parsing path: {:?}", path);
-                            }
-                        }
-                    }
-                },
-                Err(_) => {
-                    println!("Problem with executing command containing \"*\" pattern");
+        };
+
+        match enable {
+            Some(enable) => *value = enable,
+            None if quiet => status = if *value { status } else { 1 },
+            None if print => {
+                let _ = writeln!(stdio.stdout, "shopt {} {}", if *value { "-s" } else { "-u" }, name);
+            }
+            None => {
+                let _ = writeln!(stdio.stdout, "{:<15}\t{}", name, if *value { "on" } else { "off" });
+                if !*value && !listing {
+                    status = 1;
+                }
+            }
+        }
+    }
+
+    status
+}
+
+pub fn shift(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let count = match args.first().map(|arg| arg.parse::<usize>()) {
+        None => 1,
+        Some(Ok(count)) => count,
+        Some(Err(_)) => {
+            let _ = writeln!(stdio.stderr, "shsh: shift: {}: numeric argument required", args[0]);
+            return 1
+        }
+    };
+
+    if count > shell.positional.len() {
+        return 1
+    }
+
+    shell.positional.drain(..count);
+    0
+}
+
+pub fn loop_control(shell: &mut Shell, name: &str, args: &[String], stdio: &mut Stdio) -> i32 {
+    let count = match args.first().map(|arg| arg.parse::<usize>()) {
+        None => 1,
+        Some(Ok(count)) if count > 0 => count,
+        Some(Ok(_)) => {
+            let _ = writeln!(stdio.stderr, "shsh: {}: {}: loop count out of range", name, args[0]);
+            return 1
+        }
+        Some(Err(_)) => {
+            let _ = writeln!(stdio.stderr, "shsh: {}: {}: numeric argument required", name, args[0]);
+            return 1
+        }
+    };
+
+    if shell.loop_depth == 0 {
+        let _ = writeln!(stdio.stderr, "shsh: {}: only meaningful in a `for', `while', or `until' loop", name);
+        return 0
+    }
+
+    let count = count.min(shell.loop_depth);
+    shell.flow = Some(if name == "break" { Flow::Break(count) } else { Flow::Continue(count) });
+    0
+}
+
+enum Stage {
+    Running(i32),
+    Done(i32),
+}
+
+enum Step<'a> {
+    Simple(Command),
+    Compound(&'a ast::Compound, &'a [ast::Redirect]),
+    Function(&'a str, &'a ast::List),
+}
+
+fn exec_step(shell: &mut Shell, step: Step) -> i32 {
+    match step {
+        Step::Simple(cmd) => match shell.builtins.get(&cmd.name) {
+            Some(builtin) => builtins::run(shell, builtin.as_ref(), &cmd.argv()),
+            None => 0,
+        },
+        Step::Compound(compound, redirects) => exec_compound(shell, compound, redirects),
+        Step::Function(name, body) => {
+            shell.functions.insert(name.to_string(), Rc::new(body.clone()));
+            0
+        }
+    }
+}
+
+fn spawn_stage(shell: &mut Shell, pgid: Option<&mut i32>, background: bool, close_fds: &[RawFd], run: impl FnOnce(&mut Shell) -> i32) -> Stage {
+    match fork_child(shell, pgid.as_deref().copied(), background, close_fds, run) {
+        Ok(pid) => {
+            if let Some(pgid) = pgid.filter(|pgid| **pgid == 0) {
+                *pgid = pid;
+            }
+            Stage::Running(pid)
+        }
+        Err(err) => {
+            eprintln!("shsh: fork: {}", err);
+            Stage::Done(1)
+        }
+    }
+}
+
+fn spawn_error(name: &str, err: std::io::Error) -> i32 {
+    match err.kind() {
+        ErrorKind::NotFound => {
+            eprintln!("shsh: {}: command not found", name);
+            127
+        }
+        ErrorKind::PermissionDenied => {
+            eprintln!("shsh: {}: Permission denied", name);
+            126
+        }
+        _ => {
+            eprintln!("Problem with executing command: {}", err);
+            126
+        }
+    }
+}
+
+fn wait_stages(shell: &mut Shell, stages: Vec<Stage>, pgid: i32, command: String) -> Vec<i32> {
+    let mut pipestatus = vec![];
+    let mut stopped = vec![];
+    let mut interrupted = false;
+
+    for stage in stages {
+        match stage {
+            Stage::Running(pid) => match jobs::wait_pid(pid, libc::WUNTRACED) {
+                Some(ProcessState::Exited(status)) => {
+                    interrupted |= status == 128 + libc::SIGINT;
+                    pipestatus.push(status);
+                }
+                Some(ProcessState::Stopped) => {
+                    stopped.push(pid);
+                    pipestatus.push(128 + libc::SIGTSTP);
+                }
+                _ => pipestatus.push(0),
+            },
+            Stage::Done(status) => pipestatus.push(status),
+        }
+    }
+
+    let modes = match &shell.terminal {
+        Some(terminal) if pgid != 0 => Some(terminal.reclaim()),
+        _ => None,
+    };
+
+    if !stopped.is_empty() {
+        shell.jobs.add(pgid, stopped, command).stop(modes);
+
+        println!();
+        println!("{}", shell.jobs.format(shell.jobs.len() - 1, false));
+    } else if interrupted {
+        println!();
+        if shell.interactive {
+            shell.flow = Some(Flow::Interrupt);
+        }
+    }
+
+    pipestatus
+}
+
+fn exec_command(shell: &mut Shell, steps: VecDeque<Step>, background: bool) -> (Vec<Stage>, i32) {
+    let mut stages = vec![];
+    let mut stdin = None;
+    let mut pgid = 0;
+    let count = steps.len();
+    let grouped = background || shell.terminal.is_some();
+    let tty = foreground_tty(shell, background);
+
+    for (i, step) in steps.into_iter().enumerate() {
+        let mut fds = SavedFds::new();
+
+        let mut next_stdin = None;
+        if i + 1 < count {
+            match redirect::pipe() {
+                Ok((read, write)) => {
+                    let _ = fds.redirect(write, 1);
+                    next_stdin = Some(read);
                 }
+                Err(err) => eprintln!("shsh: cannot make pipe: {}", err),
+            }
+        }
+        if let Some(read) = stdin.take() {
+            let _ = fds.redirect(read, 0);
+        }
+
+        let mut close_fds = fds.saved_fds();
+        close_fds.extend(next_stdin.as_ref().map(|fd: &OwnedFd| fd.as_raw_fd()));
+
+        let mut cmd = match step {
+            Step::Simple(cmd) => cmd,
+            step => {
+                stages.push(spawn_stage(shell, grouped.then_some(&mut pgid), background, &close_fds, |shell| exec_step(shell, step)));
+                fds.restore();
+                stdin = next_stdin;
+                continue;
             }
+        };
+
+        if shell.options.xtrace {
+            trace(shell, &cmd);
         }
 
-        let child = Command::new(cmd.name).args(parsed_args).stdout(stdout).stdin(stdin).spawn();
+        if let Err(err) = fds.apply(shell, &cmd.redirects) {
+            eprintln!("shsh: {}", err);
+            fds.restore();
+            stages.push(Stage::Done(1));
+            stdin = next_stdin;
+            continue;
+        }
 
-        match child {
-            Ok(mut child) => {
-                child
-                    .wait()
-                    .expect("Problem with waiting for child");
-                prev_cmd = Some(child);
+        if cmd.name.is_empty() {
+            let assignments = std::mem::take(&mut cmd.assignments);
+            let run = move |shell: &mut Shell| match assign(shell, &assignments) {
+                0 => shell.subst_status.unwrap_or(0),
+                status => status,
+            };
 
+            match count == 1 && !background {
+                true => stages.push(Stage::Done(run(shell))),
+                false => stages.push(spawn_stage(shell, grouped.then_some(&mut pgid), background, &close_fds, run)),
             }
+            fds.restore();
+            stdin = next_stdin;
+            continue;
+        }
+
+        let saved = match push_assignments(shell, &cmd.assignments) {
+            Ok(saved) => saved,
             Err(err) => {
-                println!("Problem with executing command: {}", err);
-                prev_cmd = None;
+                eprintln!("shsh: {}", err);
+                fds.restore();
+                stages.push(Stage::Done(1));
+                stdin = next_stdin;
+                continue;
+            }
+        };
+
+        let mut lookup_functions = true;
+        while cmd.name == "command" && shell.builtins.contains("command") && cmd.args.first().is_some_and(|arg| !arg.starts_with('-')) {
+            cmd.name = cmd.args.remove(0);
+            lookup_functions = false;
+        }
+
+        if let Some(body) = shell.functions.get(&cmd.name).filter(|_| lookup_functions).cloned() {
+            let args = std::mem::take(&mut cmd.args);
+
+            if count == 1 && !background {
+                let status = functions::call(shell, &body, args);
+                pop_assignments(shell, saved);
+                fds.restore();
+                stages.push(Stage::Done(status));
+                continue;
+            }
+
+            stages.push(spawn_stage(shell, grouped.then_some(&mut pgid), background, &close_fds, |shell| functions::call(shell, &body, args)));
+
+            pop_assignments(shell, saved);
+            fds.restore();
+            stdin = next_stdin;
+            continue;
+        }
+
+        if let Some(builtin) = shell.builtins.get(&cmd.name) {
+            if count == 1 && !background {
+                let status = builtins::run(shell, builtin.as_ref(), &cmd.argv());
+                pop_assignments(shell, saved);
+                match std::mem::take(&mut shell.keep_redirects) {
+                    true => fds.keep(),
+                    false => fds.restore(),
+                }
+                stages.push(Stage::Done(status));
+                continue;
+            }
+
+            stages.push(spawn_stage(shell, grouped.then_some(&mut pgid), background, &close_fds, |shell| exec_step(shell, Step::Simple(cmd))));
+            pop_assignments(shell, saved);
+            fds.restore();
+            stdin = next_stdin;
+            continue;
+        }
+
+        let mut command = process::Command::new(&cmd.name);
+        command.args(cmd.args);
+        command.env_clear().envs(shell.vars.exported());
+        if grouped {
+            let group = pgid;
+            unsafe {
+                command.pre_exec(move || {
+                    jobs::reset_child(group, tty);
+                    Ok(())
+                });
+            }
+        }
+
+        match command.spawn() {
+            Ok(child) => {
+                let pid = child.id() as i32;
+                if grouped {
+                    if pgid == 0 {
+                        pgid = pid;
+                        if let Some(terminal) = shell.terminal.as_ref().filter(|_| tty.is_some()) {
+                            terminal.give_to(pgid, None);
+                        }
+                    }
+                    unsafe { libc::setpgid(pid, pgid) };
+                }
+                stages.push(Stage::Running(pid));
             }
+            Err(err) => stages.push(Stage::Done(spawn_error(&cmd.name, err))),
         };
+
+        pop_assignments(shell, saved);
+        fds.restore();
+        stdin = next_stdin;
     }
+
+    (stages, pgid)
 }
@@ -0,0 +1,565 @@
+use std::ffi::{CStr, CString};
+use std::fmt;
+
+use glob::{glob_with, MatchOptions, Pattern};
+
+use crate::arith;
+use crate::brace;
+use crate::executor;
+use crate::lexer::{self, Param, ParamOp, ReplaceMode, Word, WordPart};
+use crate::shell::{self, Shell};
+
+pub enum ExpandError {
+    Fatal(String),
+    NoMatch(String),
+}
+
+impl From<String> for ExpandError {
+    fn from(err: String) -> Self {
+        ExpandError::Fatal(err)
+    }
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpandError::Fatal(msg) => write!(f, "{}", msg),
+            ExpandError::NoMatch(pattern) => write!(f, "no match: {}", pattern),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Field {
+    chars: Vec<(char, bool)>,
+    separators: Vec<usize>,
+    quoted: bool,
+}
+
+impl Field {
+    fn push_str(&mut self, s: &str, quoted: bool) {
+        self.chars.extend(s.chars().map(|c| (c, quoted)));
+    }
+
+    fn push_expansion(&mut self, s: &str, quoted: bool, ifs: &str) {
+        for c in s.chars() {
+            if !quoted && ifs.contains(c) {
+                self.separators.push(self.chars.len());
+            }
+            self.chars.push((c, quoted));
+        }
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().map(|(c, _)| c).collect()
+    }
+
+    fn is_removed(&self) -> bool {
+        self.chars.is_empty() && !self.quoted
+    }
+
+    fn split(self) -> Vec<Field> {
+        let mut fields = vec![];
+        let mut field = Field { quoted: self.quoted, ..Field::default() };
+        let mut delimited = false;
+
+        for (i, (c, quoted)) in self.chars.into_iter().enumerate() {
+            if !self.separators.contains(&i) {
+                field.chars.push((c, quoted));
+                field.quoted |= quoted;
+                delimited = false;
+                continue;
+            }
+
+            if matches!(c, ' ' | '\t' | '\n') {
+                if !field.is_removed() {
+                    fields.push(std::mem::take(&mut field));
+                    delimited = true;
+                }
+                continue;
+            }
+
+            if !delimited {
+                field.quoted = true;
+                fields.push(std::mem::take(&mut field));
+            }
+            delimited = false;
+        }
+
+        fields.push(field);
+        fields
+    }
+
+    fn has_glob(&self) -> bool {
+        self.chars.iter().any(|(c, quoted)| !quoted && matches!(c, '*' | '?' | '['))
+    }
+
+    fn glob_pattern(&self, globstar: bool) -> String {
+        let mut pattern = String::new();
+
+        for (i, (c, quoted)) in self.chars.iter().enumerate() {
+            let unquoted = |i: usize, expected: char| self.chars.get(i).is_some_and(|(c, quoted)| !quoted && *c == expected);
+
+            if !quoted && *c == '*' && unquoted(i + 1, '*') {
+                let start = i == 0 || unquoted(i - 1, '/');
+                let end = i + 2 == self.chars.len() || unquoted(i + 2, '/');
+                if !(globstar && start && end) || i > 0 && unquoted(i - 1, '*') {
+                    continue;
+                }
+            }
+
+            if *quoted {
+                pattern += &Pattern::escape(&c.to_string());
+            } else {
+                pattern.push(*c);
+            }
+        }
+
+        pattern
+    }
+
+    fn pattern(&self) -> String {
+        let mut pattern = String::new();
+
+        for (c, quoted) in &self.chars {
+            if *quoted {
+                pattern += &Pattern::escape(&c.to_string());
+            } else {
+                pattern.push(*c);
+            }
+        }
+
+        pattern
+    }
+}
+
+fn is_positional_list(param: &Param, quoted: bool) -> bool {
+    param.index.is_none()
+        && matches!(param.op, None | Some(ParamOp::Substring(..)))
+        && (param.name == "@" || param.name == "*" && !quoted)
+}
+
+fn lookup(shell: &Shell, param: &Param) -> Option<String> {
+    match &param.index {
+        None => shell.get_var(&param.name),
+        Some(index) => shell.get_array(&param.name).map(|values| match index.as_str() {
+            "@" | "*" => values.join(" "),
+            _ => index
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| values.get(i).cloned())
+                .unwrap_or_default(),
+        }),
+    }
+}
+
+fn require(shell: &Shell, param: &Param, value: Option<String>) -> Result<String, String> {
+    match value {
+        Some(value) => Ok(value),
+        None if shell.options.nounset => Err(format!("{}: unbound variable", param.name)),
+        None => Ok(String::new()),
+    }
+}
+
+pub fn evaluate(shell: &mut Shell, expr: &str) -> Result<i64, String> {
+    let parts = lexer::quoted_parts(expr).map_err(|err| err.to_string())?;
+
+    let mut fields = vec![Field::default()];
+    expand_parts(shell, &parts, &mut fields, true)?;
+
+    let expr = fields.iter().map(|field| field.text()).collect::<Vec<_>>().join(" ");
+    arith::evaluate(shell, &expr)
+}
+
+fn evaluate_word(shell: &mut Shell, word: &Word) -> Result<i64, String> {
+    let expr = expand_word(shell, word)?;
+    if expr.trim().is_empty() {
+        return Ok(0);
+    }
+    arith::evaluate(shell, &expr)
+}
+
+fn substring_range(shell: &mut Shell, offset: &Word, length: &Option<Word>, len: usize) -> Result<Option<(usize, usize)>, String> {
+    let len = len as i64;
+    let offset = evaluate_word(shell, offset)?;
+
+    let start = if offset < 0 { len + offset } else { offset };
+    if start < 0 || start > len {
+        return Ok(None);
+    }
+
+    let end = match length {
+        None => len,
+        Some(length) => match evaluate_word(shell, length)? {
+            length if length < 0 && len + length < start => {
+                return Err(format!("{}: substring expression < 0", length))
+            }
+            length if length < 0 => len + length,
+            length => start.saturating_add(length).min(len),
+        },
+    };
+
+    Ok(Some((start as usize, end as usize)))
+}
+
+fn slice(chars: &[char], start: usize, end: usize) -> String {
+    chars[start..end].iter().collect()
+}
+
+fn remove_prefix(value: &str, pattern: &str, longest: bool) -> String {
+    let matches = matcher(pattern);
+    let chars: Vec<char> = value.chars().collect();
+    let mut ends = 0..=chars.len();
+
+    let end = match longest {
+        true => ends.rev().find(|end| matches(&slice(&chars, 0, *end))),
+        false => ends.find(|end| matches(&slice(&chars, 0, *end))),
+    };
+
+    match end {
+        Some(end) => slice(&chars, end, chars.len()),
+        None => value.to_string(),
+    }
+}
+
+fn remove_suffix(value: &str, pattern: &str, longest: bool) -> String {
+    let matches = matcher(pattern);
+    let chars: Vec<char> = value.chars().collect();
+    let mut starts = 0..=chars.len();
+
+    let start = match longest {
+        true => starts.find(|start| matches(&slice(&chars, *start, chars.len()))),
+        false => starts.rev().find(|start| matches(&slice(&chars, *start, chars.len()))),
+    };
+
+    match start {
+        Some(start) => slice(&chars, 0, start),
+        None => value.to_string(),
+    }
+}
+
+fn replace(value: &str, pattern: &str, replacement: &str, mode: ReplaceMode) -> String {
+    let matches = matcher(pattern);
+    let chars: Vec<char> = value.chars().collect();
+    let len = chars.len();
+
+    match mode {
+        ReplaceMode::Prefix => match (0..=len).rev().find(|end| matches(&slice(&chars, 0, *end))) {
+            Some(end) => format!("{}{}", replacement, slice(&chars, end, len)),
+            None => value.to_string(),
+        },
+        ReplaceMode::Suffix => match (0..=len).find(|start| matches(&slice(&chars, *start, len))) {
+            Some(start) => format!("{}{}", slice(&chars, 0, start), replacement),
+            None => value.to_string(),
+        },
+        ReplaceMode::First | ReplaceMode::All => {
+            let mut result = String::new();
+            let mut start = 0;
+
+            while start < len {
+                match (start + 1..=len).rev().find(|end| matches(&slice(&chars, start, *end))) {
+                    Some(end) => {
+                        result += replacement;
+                        start = end;
+
+                        if mode == ReplaceMode::First {
+                            result += &slice(&chars, start, len);
+                            break;
+                        }
+                    }
+                    None => {
+                        result.push(chars[start]);
+                        start += 1;
+                    }
+                }
+            }
+
+            result
+        }
+    }
+}
+
+fn expand_param(shell: &mut Shell, param: &Param, fields: &mut Vec<Field>, quoted: bool) -> Result<(), String> {
+    let value = lookup(shell, param);
+    let is_set = |colon: bool| value.as_ref().is_some_and(|value| !colon || !value.is_empty());
+
+    let value = match &param.op {
+        None => require(shell, param, value)?,
+        Some(ParamOp::Default(colon, word)) => match is_set(*colon) {
+            true => value.unwrap_or_default(),
+            false => return expand_parts(shell, &word.parts, fields, quoted),
+        },
+        Some(ParamOp::Alternative(colon, word)) => match is_set(*colon) {
+            true => return expand_parts(shell, &word.parts, fields, quoted),
+            false => String::new(),
+        },
+        Some(ParamOp::Assign(colon, word)) => match is_set(*colon) {
+            true => value.unwrap_or_default(),
+            false => {
+                if param.index.is_some() || !shell::is_valid_name(&param.name) {
+                    return Err(format!("${}: cannot assign in this way", param.name));
+                }
+
+                let value = expand_word(shell, word)?;
+                shell.set_var(&param.name, &value)?;
+                value
+            }
+        },
+        Some(ParamOp::Error(colon, word)) => match is_set(*colon) {
+            true => value.unwrap_or_default(),
+            false => {
+                let message = expand_word(shell, word)?;
+                if message.is_empty() {
+                    return Err(format!("{}: parameter null or not set", param.name));
+                }
+                return Err(format!("{}: {}", param.name, message));
+            }
+        },
+        Some(ParamOp::Length) if param.index.is_none() && (param.name == "@" || param.name == "*") => {
+            shell.positional.len().to_string()
+        }
+        Some(ParamOp::Length) => require(shell, param, value)?.chars().count().to_string(),
+        Some(ParamOp::RemovePrefix(longest, pattern)) => {
+            let value = require(shell, param, value)?;
+            remove_prefix(&value, &expand_pattern(shell, pattern)?, *longest)
+        }
+        Some(ParamOp::RemoveSuffix(longest, pattern)) => {
+            let value = require(shell, param, value)?;
+            remove_suffix(&value, &expand_pattern(shell, pattern)?, *longest)
+        }
+        Some(ParamOp::Replace(mode, pattern, replacement)) => {
+            let value = require(shell, param, value)?;
+            let pattern = expand_pattern(shell, pattern)?;
+            let replacement = expand_word(shell, replacement)?;
+            replace(&value, &pattern, &replacement, *mode)
+        }
+        Some(ParamOp::Substring(offset, length)) => {
+            let chars: Vec<char> = require(shell, param, value)?.chars().collect();
+            match substring_range(shell, offset, length, chars.len())? {
+                Some((start, end)) => slice(&chars, start, end),
+                None => String::new(),
+            }
+        }
+    };
+
+    fields.last_mut().unwrap().push_expansion(&value, quoted, &shell.ifs());
+    Ok(())
+}
+
+fn positional_list(shell: &mut Shell, param: &Param) -> Result<Vec<String>, String> {
+    match &param.op {
+        Some(ParamOp::Substring(offset, length)) => {
+            let list: Vec<String> = std::iter::once(shell.name.clone()).chain(shell.positional.clone()).collect();
+
+            Ok(match substring_range(shell, offset, length, list.len())? {
+                Some((start, end)) => list[start..end].to_vec(),
+                None => vec![],
+            })
+        }
+        _ => Ok(shell.positional.clone()),
+    }
+}
+
+fn expand_parts(shell: &mut Shell, parts: &[WordPart], fields: &mut Vec<Field>, quoted: bool) -> Result<(), String> {
+    for part in parts {
+        let field = fields.last_mut().unwrap();
+
+        match part {
+            WordPart::Literal(s) => field.push_str(s, quoted),
+            WordPart::SingleQuoted(s) => {
+                field.push_str(s, true);
+                field.quoted = true;
+            }
+            WordPart::Escaped(c) => field.chars.push((*c, true)),
+            WordPart::DoubleQuoted(parts) => {
+                if !parts.iter().any(|part| matches!(part, WordPart::Param(param) if is_positional_list(param, true))) {
+                    field.quoted = true;
+                }
+                expand_parts(shell, parts, fields, true)?;
+            }
+            WordPart::Param(param) if is_positional_list(param, quoted) => {
+                for (i, arg) in positional_list(shell, param)?.iter().enumerate() {
+                    if i > 0 {
+                        fields.push(Field::default());
+                    }
+
+                    let field = fields.last_mut().unwrap();
+                    field.push_expansion(arg, quoted, &shell.ifs());
+                    field.quoted |= quoted;
+                }
+            }
+            WordPart::Param(param) => expand_param(shell, param, fields, quoted)?,
+            WordPart::CommandSubst(source) => {
+                let output = executor::capture_output(shell, source)?;
+                fields.last_mut().unwrap().push_expansion(&output, quoted, &shell.ifs());
+            }
+            WordPart::ProcessSubst(output, source) => {
+                let path = executor::process_subst(shell, *output, source)?;
+                fields.last_mut().unwrap().push_str(&path, true);
+            }
+            WordPart::Arith(expr) => {
+                let value = evaluate(shell, expr)?;
+                fields.last_mut().unwrap().push_expansion(&value.to_string(), quoted, &shell.ifs());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn passwd_home(user: Option<&str>) -> Option<String> {
+    let entry = match user {
+        Some(user) => {
+            let name = CString::new(user).ok()?;
+            unsafe { libc::getpwnam(name.as_ptr()) }
+        }
+        None => unsafe { libc::getpwuid(libc::getuid()) },
+    };
+
+    if entry.is_null() {
+        return None;
+    }
+    let dir = unsafe { CStr::from_ptr((*entry).pw_dir) };
+    Some(dir.to_string_lossy().into_owned())
+}
+
+fn tilde_dir(shell: &Shell, prefix: &str) -> Option<String> {
+    match prefix {
+        "" => shell.get_var("HOME").or_else(|| passwd_home(None)),
+        "+" => shell.get_var("PWD"),
+        "-" => shell.get_var("OLDPWD"),
+        user => passwd_home(Some(user)),
+    }
+}
+
+fn expand_tildes(shell: &Shell, word: &Word, assignment: bool) -> Word {
+    let mut parts = vec![];
+
+    for (i, part) in word.parts.iter().enumerate() {
+        let text = match part {
+            WordPart::Literal(text) if i == 0 || assignment => text,
+            part => {
+                parts.push(part.clone());
+                continue;
+            }
+        };
+
+        let mut literal = String::new();
+        let mut rest = text.as_str();
+        let mut start = i == 0;
+
+        loop {
+            if start && rest.starts_with('~') {
+                let end = rest.find(|c| c == '/' || assignment && c == ':');
+                let complete = end.is_some() || i + 1 == word.parts.len();
+                let end = end.unwrap_or(rest.len());
+
+                if let Some(dir) = tilde_dir(shell, &rest[1..end]).filter(|_| complete) {
+                    if !literal.is_empty() {
+                        parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(WordPart::SingleQuoted(dir));
+                    rest = &rest[end..];
+                }
+            }
+
+            match rest.find(':').filter(|_| assignment) {
+                Some(colon) => {
+                    literal += &rest[..=colon];
+                    rest = &rest[colon + 1..];
+                    start = true;
+                }
+                None => {
+                    literal += rest;
+                    break;
+                }
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(WordPart::Literal(literal));
+        }
+    }
+
+    Word { parts }
+}
+
+fn expand_fields(shell: &mut Shell, word: &Word) -> Result<Vec<Field>, String> {
+    let mut fields = vec![Field::default()];
+    let word = expand_tildes(shell, word, false);
+
+    expand_parts(shell, &word.parts, &mut fields, false)?;
+    Ok(fields)
+}
+
+pub fn expand_assignment(shell: &mut Shell, word: &Word) -> Result<String, String> {
+    expand_word(shell, &expand_tildes(shell, word, true))
+}
+
+pub fn expand_word(shell: &mut Shell, word: &Word) -> Result<String, String> {
+    let fields = expand_fields(shell, word)?;
+    Ok(fields.iter().map(|field| field.text()).collect::<Vec<_>>().join(" "))
+}
+
+pub fn expand_pattern(shell: &mut Shell, word: &Word) -> Result<String, String> {
+    let fields = expand_fields(shell, word)?;
+    Ok(fields.iter().map(|field| field.pattern()).collect::<Vec<_>>().join(" "))
+}
+
+fn matcher(pattern: &str) -> impl Fn(&str) -> bool {
+    let options = MatchOptions { case_sensitive: true, require_literal_separator: false, require_literal_leading_dot: false };
+    let compiled = Pattern::new(pattern).ok();
+    let pattern = pattern.to_string();
+
+    move |text| match &compiled {
+        Some(compiled) => compiled.matches_with(text, options),
+        None => pattern == text,
+    }
+}
+
+pub fn matches_pattern(pattern: &str, text: &str) -> bool {
+    matcher(pattern)(text)
+}
+
+fn expand_glob(shell: &Shell, field: &Field) -> Result<Vec<String>, ExpandError> {
+    let options = MatchOptions { case_sensitive: true, require_literal_separator: true, require_literal_leading_dot: !shell.shopt.dotglob };
+
+    let mut pattern = field.glob_pattern(shell.shopt.globstar);
+    if pattern == "**" || pattern.ends_with("/**") {
+        pattern += "/*";
+    }
+
+    let mut matches: Vec<String> = match glob_with(&pattern, options) {
+        Ok(paths) => paths.filter_map(Result::ok).map(|path| path.display().to_string()).collect(),
+        Err(_) => vec![],
+    };
+    matches.sort();
+
+    if !matches.is_empty() || shell.shopt.nullglob {
+        return Ok(matches);
+    }
+    if shell.shopt.failglob {
+        return Err(ExpandError::NoMatch(field.text()));
+    }
+    Ok(vec![field.text()])
+}
+
+pub fn expand_words(shell: &mut Shell, words: &[Word]) -> Result<Vec<String>, ExpandError> {
+    let mut expanded = vec![];
+
+    for word in words.iter().flat_map(brace::expand_braces) {
+        for field in expand_fields(shell, &word)?.into_iter().flat_map(Field::split) {
+            if field.is_removed() {
+                continue;
+            }
+
+            match field.has_glob() {
+                true => expanded.extend(expand_glob(shell, &field)?),
+                false => expanded.push(field.text()),
+            }
+        }
+    }
+
+    Ok(expanded)
+}
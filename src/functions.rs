@@ -0,0 +1,44 @@
+use std::io::Write;
+
+use crate::ast;
+use crate::builtins::Stdio;
+use crate::executor;
+use crate::shell::{Flow, Shell};
+
+pub fn call(shell: &mut Shell, body: &ast::List, args: Vec<String>) -> i32 {
+    let positional = std::mem::replace(&mut shell.positional, args);
+    shell.locals.push(vec![]);
+
+    let status = executor::exec_list(shell, body);
+    if shell.flow == Some(Flow::Return) {
+        shell.flow = None;
+    }
+
+    for (name, var) in shell.locals.pop().unwrap_or_default().into_iter().rev() {
+        shell.vars.restore(&name, var);
+    }
+    shell.positional = positional;
+
+    status
+}
+
+pub fn ret(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    if shell.locals.is_empty() && shell.source_depth == 0 {
+        let _ = writeln!(stdio.stderr, "shsh: return: can only `return' from a function or sourced script");
+        return 1
+    }
+
+    let status = match args.first() {
+        None => shell.last_status,
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(status) => status & 0xff,
+            Err(_) => {
+                let _ = writeln!(stdio.stderr, "shsh: return: {}: numeric argument required", arg);
+                2
+            }
+        },
+    };
+
+    shell.flow = Some(Flow::Return);
+    status
+}
@@ -6,7 +6,7 @@ use crate::executor;
 
 pub fn get_lines_num() -> usize {
     let path = executor::get_env("HOME".to_string());
-    match OpenOptions::new().read(true).open(path+&"/.shsh_history") {
+    match OpenOptions::new().read(true).open(path + "/.shsh_history") {
         Ok(f) => {
             BufReader::new(f).lines().count()
         },
@@ -25,11 +25,10 @@ impl History {
 
     pub fn write_history(&self) {
         let path = executor::get_env("HOME".to_string());
-        match OpenOptions::new().create(true).append(true).open(path+&"/.shsh_history") {
+        match OpenOptions::new().create(true).append(true).open(path + "/.shsh_history") {
             Ok(mut f) => {
-                match writeln!(&mut f, "{}", &self.history_string) {
-                    Err(e) => panic!("Problem with writing to file, {}", e),
-                    _ => (),
+                if let Err(e) = writeln!(&mut f, "{}", &self.history_string) {
+                    panic!("Problem with writing to file, {}", e);
                 }
             },
             Err(_) => panic!("Error with writing history, cannot open file"),
@@ -38,10 +37,10 @@ impl History {
 
     pub fn get_history(&self, pos: i32) -> String {
         let path = executor::get_env("HOME".to_string());
-        match OpenOptions::new().read(true).open(path+&"/.shsh_history") {
+        match OpenOptions::new().read(true).open(path + "/.shsh_history") {
             Ok(f) => {
                 let mut rev_lines = RevLines::new(f);
-                return rev_lines.nth(pos as usize).unwrap().unwrap();
+                rev_lines.nth(pos as usize).unwrap().unwrap()
             },
             Err(_) => panic!("Error with getting history, cannot open file"),
         }
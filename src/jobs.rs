@@ -0,0 +1,563 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::builtins::Stdio;
+use crate::shell::Shell;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProcessState {
+    Running,
+    Stopped,
+    Exited(i32),
+}
+
+pub struct Process {
+    pub pid: i32,
+    pub state: ProcessState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(i32),
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobState::Running => write!(f, "Running"),
+            JobState::Stopped => write!(f, "Stopped"),
+            JobState::Done(0) => write!(f, "Done"),
+            JobState::Done(status) => write!(f, "Exit {}", status),
+        }
+    }
+}
+
+const JOB_SIGNALS: [libc::c_int; 5] = [libc::SIGINT, libc::SIGQUIT, libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU];
+
+static CHILD_EVENT: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigchld(_: libc::c_int) {
+    CHILD_EVENT.store(true, Ordering::SeqCst);
+}
+
+pub struct Terminal {
+    fd: RawFd,
+    pgid: i32,
+    modes: libc::termios,
+}
+
+impl Terminal {
+    pub fn init() -> Option<Self> {
+        let fd = libc::STDIN_FILENO;
+
+        unsafe {
+            if libc::isatty(fd) == 0 {
+                return None;
+            }
+
+            while libc::tcgetpgrp(fd) != libc::getpgrp() {
+                libc::kill(-libc::getpgrp(), libc::SIGTTIN);
+            }
+
+            for signal in JOB_SIGNALS {
+                libc::signal(signal, libc::SIG_IGN);
+            }
+
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigchld as *const () as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGCHLD, &action, std::ptr::null_mut());
+
+            let pid = libc::getpid();
+            if libc::getpgrp() != pid {
+                libc::setpgid(0, pid);
+            }
+
+            let pgid = libc::getpgrp();
+            libc::tcsetpgrp(fd, pgid);
+
+            let mut modes = std::mem::zeroed();
+            libc::tcgetattr(fd, &mut modes);
+
+            Some(Self { fd, pgid, modes })
+        }
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    pub fn give_to(&self, pgid: i32, modes: Option<&libc::termios>) {
+        unsafe {
+            if let Some(modes) = modes {
+                libc::tcsetattr(self.fd, libc::TCSADRAIN, modes);
+            }
+            libc::tcsetpgrp(self.fd, pgid);
+        }
+    }
+
+    pub fn reclaim(&self) -> libc::termios {
+        unsafe {
+            let mut job_modes = std::mem::zeroed();
+            libc::tcgetattr(self.fd, &mut job_modes);
+
+            libc::tcsetpgrp(self.fd, self.pgid);
+            libc::tcsetattr(self.fd, libc::TCSADRAIN, &self.modes);
+
+            job_modes
+        }
+    }
+}
+
+pub fn reset_child(pgid: i32, foreground: Option<RawFd>) {
+    unsafe {
+        libc::setpgid(0, pgid);
+        if let Some(fd) = foreground {
+            libc::tcsetpgrp(fd, libc::getpgrp());
+        }
+    }
+    reset_signals();
+}
+
+pub fn reset_signals() {
+    unsafe {
+        for signal in JOB_SIGNALS {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+        libc::signal(libc::SIGCHLD, libc::SIG_DFL);
+    }
+}
+
+pub struct Job {
+    pub id: usize,
+    pub pgid: i32,
+    pub processes: Vec<Process>,
+    pub command: String,
+    pub modes: Option<libc::termios>,
+    changed: bool,
+}
+
+impl Job {
+    pub fn state(&self) -> JobState {
+        if self.processes.iter().any(|process| process.state == ProcessState::Running) {
+            return JobState::Running;
+        }
+        if self.processes.iter().any(|process| process.state == ProcessState::Stopped) {
+            return JobState::Stopped;
+        }
+
+        match self.processes.last().map(|process| process.state) {
+            Some(ProcessState::Exited(status)) => JobState::Done(status),
+            _ => JobState::Done(0),
+        }
+    }
+
+    pub fn last_pid(&self) -> i32 {
+        self.processes.last().map(|process| process.pid).unwrap_or(self.pgid)
+    }
+
+    fn update(&mut self) {
+        for process in &mut self.processes {
+            if matches!(process.state, ProcessState::Exited(_)) {
+                continue;
+            }
+
+            if let Some(state) = wait_pid(process.pid, libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED) {
+                self.changed |= state != process.state;
+                process.state = state;
+            }
+        }
+    }
+
+    pub fn stop(&mut self, modes: Option<libc::termios>) {
+        for process in &mut self.processes {
+            process.state = ProcessState::Stopped;
+        }
+        self.modes = modes;
+    }
+
+    pub fn wait(&mut self) -> JobState {
+        for process in &mut self.processes {
+            if process.state == ProcessState::Running {
+                if let Some(state) = wait_pid(process.pid, libc::WUNTRACED) {
+                    process.state = state;
+                }
+            }
+        }
+
+        self.state()
+    }
+
+    pub fn resume(&mut self) -> io::Result<()> {
+        if unsafe { libc::kill(-self.pgid, libc::SIGCONT) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        for process in &mut self.processes {
+            if process.state == ProcessState::Stopped {
+                process.state = ProcessState::Running;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct JobTable {
+    jobs: Vec<Job>,
+    inherited: bool,
+}
+
+impl JobTable {
+    pub fn new() -> Self {
+        Self { jobs: vec![], inherited: false }
+    }
+
+    pub fn inherit(&mut self) {
+        self.inherited = true;
+    }
+
+    pub fn add(&mut self, pgid: i32, pids: Vec<i32>, command: String) -> &mut Job {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        let processes = pids.into_iter().map(|pid| Process { pid, state: ProcessState::Running }).collect();
+
+        self.jobs.push(Job { id, pgid, processes, command, modes: None, changed: false });
+        self.jobs.last_mut().unwrap()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn update(&mut self) {
+        if self.inherited {
+            return;
+        }
+        for job in &mut self.jobs {
+            job.update();
+        }
+    }
+
+    fn marker(&self, index: usize) -> char {
+        let len = self.jobs.len();
+
+        if index + 1 == len {
+            '+'
+        } else if index + 2 == len {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    pub fn format(&self, index: usize, long: bool) -> String {
+        let job = &self.jobs[index];
+        let state = job.state();
+
+        let mut command = job.command.clone();
+        if state == JobState::Running {
+            command += " &";
+        }
+
+        if long {
+            format!("[{}]{} {:<6} {:<24}{}", job.id, self.marker(index), job.pgid, state.to_string(), command)
+        } else {
+            format!("[{}]{}  {:<24}{}", job.id, self.marker(index), state.to_string(), command)
+        }
+    }
+
+    pub fn notify(&mut self) {
+        if !CHILD_EVENT.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        self.update();
+
+        let mut index = 0;
+        while index < self.jobs.len() {
+            match self.jobs[index].state() {
+                JobState::Done(_) => {
+                    println!("{}", self.format(index, false));
+                    self.jobs.remove(index);
+                    continue;
+                }
+                JobState::Stopped if self.jobs[index].changed => println!("{}", self.format(index, false)),
+                _ => (),
+            }
+
+            self.jobs[index].changed = false;
+            index += 1;
+        }
+    }
+
+    pub fn find(&self, spec: Option<&str>) -> Result<usize, String> {
+        let spec = spec.unwrap_or("%%");
+        let not_found = || format!("{}: no such job", spec);
+
+        if self.jobs.is_empty() {
+            return Err(if spec == "%%" { "current: no such job".to_string() } else { not_found() });
+        }
+
+        let index = match spec.strip_prefix('%').unwrap_or(spec) {
+            "" | "%" | "+" => Some(self.jobs.len() - 1),
+            "-" => self.jobs.len().checked_sub(2).or(Some(self.jobs.len() - 1)),
+            name => match name.parse::<usize>() {
+                Ok(id) => self.jobs.iter().position(|job| job.id == id),
+                Err(_) => match name.strip_prefix('?') {
+                    Some(text) => self.jobs.iter().rposition(|job| job.command.contains(text)),
+                    None => self.jobs.iter().rposition(|job| job.command.starts_with(name)),
+                },
+            },
+        };
+
+        index.ok_or_else(not_found)
+    }
+
+    pub fn find_pid(&self, pid: i32) -> Option<usize> {
+        self.jobs.iter().position(|job| job.processes.iter().any(|process| process.pid == pid))
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut Job {
+        &mut self.jobs[index]
+    }
+
+    pub fn make_current(&mut self, index: usize) -> usize {
+        let job = self.jobs.remove(index);
+        self.jobs.push(job);
+        self.jobs.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Job {
+        self.jobs.remove(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+}
+
+pub fn decode_status(status: libc::c_int) -> ProcessState {
+    if libc::WIFEXITED(status) {
+        ProcessState::Exited(libc::WEXITSTATUS(status))
+    } else if libc::WIFSIGNALED(status) {
+        ProcessState::Exited(128 + libc::WTERMSIG(status))
+    } else if libc::WIFSTOPPED(status) {
+        ProcessState::Stopped
+    } else {
+        ProcessState::Running
+    }
+}
+
+pub fn wait_pid(pid: i32, flags: libc::c_int) -> Option<ProcessState> {
+    let mut status = 0;
+
+    loop {
+        match unsafe { libc::waitpid(pid, &mut status, flags) } {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            -1 => return Some(ProcessState::Exited(127)),
+            0 => return None,
+            _ => return Some(decode_status(status)),
+        }
+    }
+}
+
+fn job_index(shell: &Shell, name: &str, spec: Option<&String>, stdio: &mut Stdio) -> Option<usize> {
+    match shell.jobs.find(spec.map(|spec| spec.as_str())) {
+        Ok(index) => Some(index),
+        Err(err) => {
+            let _ = writeln!(stdio.stderr, "shsh: {}: {}", name, err);
+            None
+        }
+    }
+}
+
+pub fn jobs(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let long = args.iter().any(|arg| arg == "-l");
+    let pids_only = args.iter().any(|arg| arg == "-p");
+
+    shell.jobs.update();
+
+    for index in 0..shell.jobs.len() {
+        if pids_only {
+            let _ = writeln!(stdio.stdout, "{}", shell.jobs.jobs[index].pgid);
+        } else {
+            let _ = writeln!(stdio.stdout, "{}", shell.jobs.format(index, long));
+        }
+    }
+
+    let mut index = 0;
+    while index < shell.jobs.len() {
+        if let JobState::Done(_) = shell.jobs.jobs[index].state() {
+            shell.jobs.remove(index);
+        } else {
+            shell.jobs.jobs[index].changed = false;
+            index += 1;
+        }
+    }
+
+    0
+}
+
+pub fn fg(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let index = match job_index(shell, "fg", args.first(), stdio) {
+        Some(index) => index,
+        None => return 1,
+    };
+
+    let index = shell.jobs.make_current(index);
+    let job = shell.jobs.get_mut(index);
+    let _ = writeln!(stdio.stdout, "{}", job.command);
+
+    if let Some(terminal) = &shell.terminal {
+        terminal.give_to(job.pgid, job.modes.as_ref());
+    }
+
+    if let Err(err) = job.resume() {
+        let _ = writeln!(stdio.stderr, "shsh: fg: {}", err);
+        return 1;
+    }
+
+    let state = job.wait();
+    if let Some(terminal) = &shell.terminal {
+        job.modes = Some(terminal.reclaim());
+    }
+
+    match state {
+        JobState::Done(status) => {
+            shell.jobs.remove(index);
+            if status == 128 + libc::SIGINT {
+                let _ = writeln!(stdio.stdout);
+            }
+            status
+        }
+        JobState::Stopped => {
+            let _ = writeln!(stdio.stdout);
+            let _ = writeln!(stdio.stdout, "{}", shell.jobs.format(index, false));
+            128 + libc::SIGTSTP
+        }
+        JobState::Running => 0,
+    }
+}
+
+pub fn bg(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut status = 0;
+    let specs: Vec<Option<&String>> = if args.is_empty() { vec![None] } else { args.iter().map(Some).collect() };
+
+    for spec in specs {
+        let index = match job_index(shell, "bg", spec, stdio) {
+            Some(index) => shell.jobs.make_current(index),
+            None => {
+                status = 1;
+                continue;
+            }
+        };
+
+        let job = shell.jobs.get_mut(index);
+        if let Err(err) = job.resume() {
+            let _ = writeln!(stdio.stderr, "shsh: bg: {}", err);
+            status = 1;
+            continue;
+        }
+        let _ = writeln!(stdio.stdout, "[{}]+ {} &", job.id, job.command);
+    }
+
+    status
+}
+
+pub fn wait(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    if args.is_empty() {
+        while !shell.jobs.is_empty() {
+            let mut job = shell.jobs.remove(0);
+            job.wait();
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let index = if arg.starts_with('%') {
+            match job_index(shell, "wait", Some(arg), stdio) {
+                Some(index) => index,
+                None => {
+                    status = 127;
+                    continue;
+                }
+            }
+        } else {
+            let pid = match arg.parse::<i32>() {
+                Ok(pid) => pid,
+                Err(_) => {
+                    let _ = writeln!(stdio.stderr, "shsh: wait: `{}': not a pid or valid job spec", arg);
+                    status = 2;
+                    continue;
+                }
+            };
+
+            match shell.jobs.find_pid(pid) {
+                Some(index) => index,
+                None => {
+                    let _ = writeln!(stdio.stderr, "shsh: wait: pid {} is not a child of this shell", pid);
+                    status = 127;
+                    continue;
+                }
+            }
+        };
+
+        let mut job = shell.jobs.remove(index);
+        status = match job.wait() {
+            JobState::Done(code) => code,
+            _ => 0,
+        };
+    }
+
+    status
+}
+
+pub fn disown(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut specs = vec![];
+    let mut all = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "-a" if specs.is_empty() => all = true,
+            _ if arg.starts_with('-') && arg.len() > 1 && specs.is_empty() => {
+                let _ = writeln!(stdio.stderr, "shsh: disown: {}: invalid option", arg);
+                let _ = writeln!(stdio.stderr, "disown: usage: disown [-a] [jobspec ...]");
+                return 2
+            }
+            _ => specs.push(arg),
+        }
+    }
+
+    if all {
+        while !shell.jobs.is_empty() {
+            shell.jobs.remove(0);
+        }
+        return 0;
+    }
+
+    if specs.is_empty() {
+        return match job_index(shell, "disown", None, stdio) {
+            Some(index) => {
+                shell.jobs.remove(index);
+                0
+            }
+            None => 1,
+        };
+    }
+
+    let mut status = 0;
+    for spec in specs {
+        match job_index(shell, "disown", Some(spec), stdio) {
+            Some(index) => {
+                shell.jobs.remove(index);
+            }
+            None => status = 1,
+        }
+    }
+
+    status
+}
@@ -0,0 +1,989 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ast;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordPart {
+    Literal(String),
+    SingleQuoted(String),
+    Escaped(char),
+    DoubleQuoted(Vec<WordPart>),
+    Param(Param),
+    CommandSubst(String),
+    ProcessSubst(bool, String),
+    Arith(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaceMode {
+    First,
+    All,
+    Prefix,
+    Suffix,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamOp {
+    Length,
+    Default(bool, Word),
+    Assign(bool, Word),
+    Error(bool, Word),
+    Alternative(bool, Word),
+    RemovePrefix(bool, Word),
+    RemoveSuffix(bool, Word),
+    Replace(ReplaceMode, Word, Word),
+    Substring(Word, Option<Word>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub index: Option<String>,
+    pub op: Option<ParamOp>,
+}
+
+impl fmt::Display for ParamOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let colon = |colon: &bool| if *colon { ":" } else { "" };
+        let double = |double: &bool, c: &str| if *double { c.repeat(2) } else { c.to_string() };
+
+        match self {
+            ParamOp::Length => Ok(()),
+            ParamOp::Default(c, word) => write!(f, "{}-{}", colon(c), word),
+            ParamOp::Assign(c, word) => write!(f, "{}={}", colon(c), word),
+            ParamOp::Error(c, word) => write!(f, "{}?{}", colon(c), word),
+            ParamOp::Alternative(c, word) => write!(f, "{}+{}", colon(c), word),
+            ParamOp::RemovePrefix(longest, pattern) => write!(f, "{}{}", double(longest, "#"), pattern),
+            ParamOp::RemoveSuffix(longest, pattern) => write!(f, "{}{}", double(longest, "%"), pattern),
+            ParamOp::Replace(mode, pattern, replacement) => {
+                let mode = match mode {
+                    ReplaceMode::First => "",
+                    ReplaceMode::All => "/",
+                    ReplaceMode::Prefix => "#",
+                    ReplaceMode::Suffix => "%",
+                };
+                write!(f, "/{}{}/{}", mode, pattern, replacement)
+            }
+            ParamOp::Substring(offset, Some(length)) => write!(f, ":{}:{}", offset, length),
+            ParamOp::Substring(offset, None) => write!(f, ":{}", offset),
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${{")?;
+        if self.op == Some(ParamOp::Length) {
+            write!(f, "#")?;
+        }
+
+        write!(f, "{}", self.name)?;
+        if let Some(index) = &self.index {
+            write!(f, "[{}]", index)?;
+        }
+        if let Some(op) = &self.op {
+            write!(f, "{}", op)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for WordPart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WordPart::Literal(s) => write!(f, "{}", s),
+            WordPart::SingleQuoted(s) => write!(f, "'{}'", s),
+            WordPart::Escaped(c) => write!(f, "\\{}", c),
+            WordPart::DoubleQuoted(parts) => {
+                write!(f, "\"")?;
+                for part in parts {
+                    write!(f, "{}", part)?;
+                }
+                write!(f, "\"")
+            }
+            WordPart::Param(param) => write!(f, "{}", param),
+            WordPart::CommandSubst(source) => write!(f, "$({})", source),
+            WordPart::ProcessSubst(output, source) => write!(f, "{}({})", if *output { '>' } else { '<' }, source),
+            WordPart::Arith(expr) => write!(f, "$(({}))", expr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for part in &self.parts {
+            write!(f, "{}", part)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Pipe,
+    And,
+    Or,
+    Semi,
+    DSemi,
+    SemiAmp,
+    DSemiAmp,
+    Amp,
+    LParen,
+    RParen,
+    Newline,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Operator::Pipe => "|",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Semi => ";",
+            Operator::DSemi => ";;",
+            Operator::SemiAmp => ";&",
+            Operator::DSemiAmp => ";;&",
+            Operator::Amp => "&",
+            Operator::LParen => "(",
+            Operator::RParen => ")",
+            Operator::Newline => "newline",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectOp {
+    Less,
+    Great,
+    DGreat,
+    Clobber,
+    LessGreat,
+    LessAnd,
+    GreatAnd,
+    AndGreat,
+    AndDGreat,
+    TLess,
+    DLess,
+    DLessDash,
+}
+
+impl fmt::Display for RedirectOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            RedirectOp::Less => "<",
+            RedirectOp::Great => ">",
+            RedirectOp::DGreat => ">>",
+            RedirectOp::Clobber => ">|",
+            RedirectOp::LessGreat => "<>",
+            RedirectOp::LessAnd => "<&",
+            RedirectOp::GreatAnd => ">&",
+            RedirectOp::AndGreat => "&>",
+            RedirectOp::AndDGreat => "&>>",
+            RedirectOp::TLess => "<<<",
+            RedirectOp::DLess => "<<",
+            RedirectOp::DLessDash => "<<-",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Word(Word),
+    Operator(Operator),
+    Redirect(Option<i32>, RedirectOp),
+    Arith(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Operator(op) => write!(f, "{}", op),
+            Token::Redirect(Some(fd), op) => write!(f, "{}{}", fd, op),
+            Token::Redirect(None, op) => write!(f, "{}", op),
+            Token::Arith(expr) => write!(f, "(({}))", expr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Incomplete,
+    Syntax(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Incomplete => write!(f, "syntax error: unexpected end of file"),
+            ParseError::Syntax(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+struct PendingHereDoc {
+    token: usize,
+    delimiter: String,
+    quoted: bool,
+    strip_tabs: bool,
+}
+
+pub struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    tokens: Vec<Token>,
+    heredoc_op: Option<bool>,
+    heredocs: Vec<PendingHereDoc>,
+}
+
+impl Lexer {
+    pub fn new(input: &str) -> Self {
+        let chars = input.chars().collect();
+        let tokens = vec![];
+
+        Self { chars, pos: 0, tokens, heredoc_op: None, heredocs: vec![] }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn is_word_end(c: char) -> bool {
+        matches!(c, ' ' | '\t' | '\n' | '|' | '&' | ';' | '(' | ')' | '<' | '>')
+    }
+
+    fn at_process_subst(&self) -> bool {
+        matches!(self.peek(), Some('<' | '>')) && self.peek_at(1) == Some('(')
+    }
+
+    fn read_redirect(&mut self, fd: Option<i32>) -> Option<Token> {
+        let c = self.peek()?;
+        let next = self.peek_at(1);
+
+        let (op, len) = match (c, next, self.peek_at(2)) {
+            ('<', Some('<'), Some('<')) => (RedirectOp::TLess, 3),
+            ('<', Some('<'), Some('-')) => (RedirectOp::DLessDash, 3),
+            ('<', Some('<'), _) => (RedirectOp::DLess, 2),
+            ('<', Some('&'), _) => (RedirectOp::LessAnd, 2),
+            ('<', Some('>'), _) => (RedirectOp::LessGreat, 2),
+            ('<', _, _) => (RedirectOp::Less, 1),
+            ('>', Some('>'), _) => (RedirectOp::DGreat, 2),
+            ('>', Some('|'), _) => (RedirectOp::Clobber, 2),
+            ('>', Some('&'), _) => (RedirectOp::GreatAnd, 2),
+            ('>', _, _) => (RedirectOp::Great, 1),
+            ('&', Some('>'), Some('>')) if fd.is_none() => (RedirectOp::AndDGreat, 3),
+            ('&', Some('>'), _) if fd.is_none() => (RedirectOp::AndGreat, 2),
+            _ => return None,
+        };
+
+        self.pos += len;
+        Some(Token::Redirect(fd, op))
+    }
+
+    fn read_io_number(&mut self) -> Option<Token> {
+        let digits = self.chars[self.pos..].iter().take_while(|c| c.is_ascii_digit()).count();
+
+        if digits == 0 || !matches!(self.peek_at(digits), Some('<' | '>')) {
+            return None;
+        }
+
+        let fd = self.chars[self.pos..self.pos + digits].iter().collect::<String>().parse().ok()?;
+        let start = self.pos;
+        self.pos += digits;
+
+        let token = self.read_redirect(Some(fd));
+        if token.is_none() {
+            self.pos = start;
+        }
+        token
+    }
+
+    fn read_operator(&mut self) -> Option<Token> {
+        if let Some(token) = self.read_redirect(None) {
+            return Some(token);
+        }
+
+        let c = self.peek()?;
+        let next = self.peek_at(1);
+
+        let (token, len) = match (c, next) {
+            (';', Some(';')) if self.peek_at(2) == Some('&') => (Token::Operator(Operator::DSemiAmp), 3),
+            (';', Some(';')) => (Token::Operator(Operator::DSemi), 2),
+            (';', Some('&')) => (Token::Operator(Operator::SemiAmp), 2),
+            ('|', Some('|')) => (Token::Operator(Operator::Or), 2),
+            ('|', _) => (Token::Operator(Operator::Pipe), 1),
+            ('&', Some('&')) => (Token::Operator(Operator::And), 2),
+            ('&', _) => (Token::Operator(Operator::Amp), 1),
+            (';', _) => (Token::Operator(Operator::Semi), 1),
+            ('(', _) => (Token::Operator(Operator::LParen), 1),
+            (')', _) => (Token::Operator(Operator::RParen), 1),
+            ('\n', _) => (Token::Operator(Operator::Newline), 1),
+            _ => return None,
+        };
+
+        self.pos += len;
+        Some(token)
+    }
+
+    fn read_arith(&mut self) -> Option<Token> {
+        if self.peek() != Some('(') || self.peek_at(1) != Some('(') {
+            return None;
+        }
+
+        let mut depth = 0;
+        let mut end = self.pos + 2;
+
+        loop {
+            match self.chars.get(end)? {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                ')' if self.chars.get(end + 1) == Some(&')') => break,
+                ')' => return None,
+                _ => (),
+            }
+            end += 1;
+        }
+
+        let expr = self.chars[self.pos + 2..end].iter().collect();
+        self.pos = end + 2;
+        Some(Token::Arith(expr))
+    }
+
+    fn is_name_start(c: char) -> bool {
+        c.is_ascii_alphabetic() || c == '_'
+    }
+
+    fn read_name(&mut self) -> String {
+        let mut name = String::new();
+
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_alphanumeric() && c != '_' {
+                break;
+            }
+            name.push(c);
+            self.pos += 1;
+        }
+
+        name
+    }
+
+    fn is_special_param(c: char) -> bool {
+        matches!(c, '?' | '!' | '@' | '*' | '#' | '$' | '0'..='9')
+    }
+
+    fn read_param_word(&mut self, stops: &[char]) -> Result<Word, ParseError> {
+        let word = self.read_parts(|c| c == '}' || stops.contains(&c))?;
+
+        match self.peek() {
+            Some(_) => Ok(word),
+            None => Err(ParseError::Incomplete),
+        }
+    }
+
+    fn read_param_op(&mut self) -> Result<Option<ParamOp>, ParseError> {
+        let colon = self.peek() == Some(':') && matches!(self.peek_at(1), Some('-' | '=' | '?' | '+'));
+        if colon {
+            self.pos += 1;
+        }
+
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(ParseError::Incomplete),
+        };
+
+        let op = match c {
+            '-' | '=' | '?' | '+' => {
+                self.pos += 1;
+                let word = self.read_param_word(&[])?;
+
+                match c {
+                    '-' => ParamOp::Default(colon, word),
+                    '=' => ParamOp::Assign(colon, word),
+                    '?' => ParamOp::Error(colon, word),
+                    _ => ParamOp::Alternative(colon, word),
+                }
+            }
+            '#' | '%' => {
+                self.pos += 1;
+                let longest = self.peek() == Some(c);
+                if longest {
+                    self.pos += 1;
+                }
+
+                let pattern = self.read_param_word(&[])?;
+                if c == '#' {
+                    ParamOp::RemovePrefix(longest, pattern)
+                } else {
+                    ParamOp::RemoveSuffix(longest, pattern)
+                }
+            }
+            '/' => {
+                self.pos += 1;
+                let mode = match self.peek() {
+                    Some('/') => ReplaceMode::All,
+                    Some('#') => ReplaceMode::Prefix,
+                    Some('%') => ReplaceMode::Suffix,
+                    _ => ReplaceMode::First,
+                };
+                if mode != ReplaceMode::First {
+                    self.pos += 1;
+                }
+
+                let pattern = self.read_param_word(&['/'])?;
+                let mut replacement = Word::default();
+                if self.peek() == Some('/') {
+                    self.pos += 1;
+                    replacement = self.read_param_word(&[])?;
+                }
+
+                ParamOp::Replace(mode, pattern, replacement)
+            }
+            ':' => {
+                self.pos += 1;
+                let offset = self.read_param_word(&[':'])?;
+                let mut length = None;
+                if self.peek() == Some(':') {
+                    self.pos += 1;
+                    length = Some(self.read_param_word(&[])?);
+                }
+
+                ParamOp::Substring(offset, length)
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(op))
+    }
+
+    fn read_braced_param(&mut self) -> Result<Param, ParseError> {
+        let length = self.peek() == Some('#')
+            && self.peek_at(1).is_some_and(|c| Self::is_name_start(c) || Self::is_special_param(c));
+        if length {
+            self.pos += 1;
+        }
+
+        let name = match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                self.chars[start..self.pos].iter().collect()
+            }
+            Some(c) if Self::is_special_param(c) => {
+                self.pos += 1;
+                c.to_string()
+            }
+            Some(c) if Self::is_name_start(c) => self.read_name(),
+            Some(_) => return Err(ParseError::Syntax("bad substitution".to_string())),
+            None => return Err(ParseError::Incomplete),
+        };
+
+        let mut index = None;
+        if self.peek() == Some('[') {
+            self.pos += 1;
+
+            let mut text = String::new();
+            loop {
+                match self.peek() {
+                    Some(']') => break,
+                    Some(c) => text.push(c),
+                    None => return Err(ParseError::Incomplete),
+                }
+                self.pos += 1;
+            }
+            self.pos += 1;
+
+            index = Some(text);
+        }
+
+        let op = match length {
+            true => Some(ParamOp::Length),
+            false => self.read_param_op()?,
+        };
+
+        match self.peek() {
+            Some('}') => {
+                self.pos += 1;
+                Ok(Param { name, index, op })
+            }
+            Some(_) => Err(ParseError::Syntax("bad substitution".to_string())),
+            None => Err(ParseError::Incomplete),
+        }
+    }
+
+    fn read_command_subst(&mut self) -> Result<String, ParseError> {
+        let mut lexer = Lexer::new(&self.chars[self.pos..].iter().collect::<String>());
+        let mut depth = 0;
+
+        while lexer.push_next_token()? {
+            match lexer.tokens.last() {
+                Some(Token::Operator(Operator::LParen)) => depth += 1,
+                Some(Token::Operator(Operator::RParen)) if depth > 0 => depth -= 1,
+                Some(Token::Operator(Operator::RParen)) => {
+                    let tokens = lexer.tokens[..lexer.tokens.len() - 1].to_vec();
+                    if matches!(ast::parse_tokens(tokens, &BTreeMap::new()), Err(ParseError::Incomplete)) {
+                        continue;
+                    }
+
+                    let source = lexer.chars[..lexer.pos - 1].iter().collect();
+                    self.pos += lexer.pos;
+                    return Ok(source);
+                }
+                _ => (),
+            }
+        }
+
+        Err(ParseError::Incomplete)
+    }
+
+    fn read_backquoted(&mut self) -> Result<String, ParseError> {
+        let mut source = String::new();
+
+        loop {
+            match self.peek() {
+                Some('`') => {
+                    self.pos += 1;
+                    return Ok(source);
+                }
+                Some('\\') if matches!(self.peek_at(1), Some('$' | '`' | '\\')) => {
+                    source.extend(self.peek_at(1));
+                    self.pos += 2;
+                }
+                Some(c) => {
+                    source.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(ParseError::Incomplete),
+            }
+        }
+    }
+
+    fn read_dollar(&mut self) -> Result<Option<WordPart>, ParseError> {
+        match self.peek_at(1) {
+            Some('(') => {
+                self.pos += 1;
+                if let Some(Token::Arith(expr)) = self.read_arith() {
+                    return Ok(Some(WordPart::Arith(expr)));
+                }
+
+                self.pos += 1;
+                Ok(Some(WordPart::CommandSubst(self.read_command_subst()?)))
+            }
+            Some('{') => {
+                self.pos += 2;
+                Ok(Some(WordPart::Param(self.read_braced_param()?)))
+            }
+            Some(c) if Self::is_special_param(c) => {
+                self.pos += 2;
+                Ok(Some(WordPart::Param(Param { name: c.to_string(), index: None, op: None })))
+            }
+            Some(c) if Self::is_name_start(c) => {
+                self.pos += 1;
+                Ok(Some(WordPart::Param(Param { name: self.read_name(), index: None, op: None })))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn read_single_quoted(&mut self) -> Result<String, ParseError> {
+        let mut text = String::new();
+
+        loop {
+            match self.peek() {
+                Some('\'') => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(ParseError::Incomplete),
+            }
+        }
+    }
+
+    fn read_digits(&mut self, radix: u32, max: usize) -> Option<u32> {
+        let mut value = None;
+
+        for _ in 0..max {
+            match self.peek().and_then(|c| c.to_digit(radix)) {
+                Some(digit) => {
+                    value = Some(value.unwrap_or(0) * radix + digit);
+                    self.pos += 1;
+                }
+                None => break,
+            }
+        }
+
+        value
+    }
+
+    fn read_ansi_c_quoted(&mut self) -> Result<String, ParseError> {
+        let mut text = String::new();
+
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(ParseError::Incomplete),
+            };
+            self.pos += 1;
+
+            match c {
+                '\'' => return Ok(text),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(c) => c,
+                        None => return Err(ParseError::Incomplete),
+                    };
+                    self.pos += 1;
+
+                    match escaped {
+                        'a' => text.push('\x07'),
+                        'b' => text.push('\x08'),
+                        'e' | 'E' => text.push('\x1b'),
+                        'f' => text.push('\x0c'),
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'v' => text.push('\x0b'),
+                        '\\' | '\'' | '"' | '?' => text.push(escaped),
+                        '0'..='7' => {
+                            self.pos -= 1;
+                            let value = self.read_digits(8, 3).unwrap_or(0);
+                            text.push(char::from_u32(value).unwrap_or('\u{fffd}'));
+                        }
+                        'x' | 'u' | 'U' => {
+                            let max = match escaped {
+                                'x' => 2,
+                                'u' => 4,
+                                _ => 8,
+                            };
+                            match self.read_digits(16, max) {
+                                Some(value) => text.push(char::from_u32(value).unwrap_or('\u{fffd}')),
+                                None => {
+                                    text.push('\\');
+                                    text.push(escaped);
+                                }
+                            }
+                        }
+                        'c' => {
+                            if let Some(control) = self.peek() {
+                                self.pos += 1;
+                                text.push(char::from_u32(control.to_ascii_uppercase() as u32 ^ 0x40).unwrap_or(control));
+                            }
+                        }
+                        _ => {
+                            text.push('\\');
+                            text.push(escaped);
+                        }
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+    }
+
+    fn read_double_quoted(&mut self) -> Result<Vec<WordPart>, ParseError> {
+        self.read_quoted_parts(Some('"'))
+    }
+
+    fn read_quoted_parts(&mut self, terminator: Option<char>) -> Result<Vec<WordPart>, ParseError> {
+        let mut parts = vec![];
+        let mut text = String::new();
+
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None if terminator.is_none() => break,
+                None => return Err(ParseError::Incomplete),
+            };
+
+            match c {
+                _ if Some(c) == terminator => {
+                    self.pos += 1;
+                    break;
+                }
+                '\\' => match self.peek_at(1) {
+                    Some('\n') => self.pos += 2,
+                    Some(next @ ('$' | '`' | '\\')) => {
+                        text.push(next);
+                        self.pos += 2;
+                    }
+                    Some(next) if Some(next) == terminator => {
+                        text.push(next);
+                        self.pos += 2;
+                    }
+                    Some(_) => {
+                        text.push(c);
+                        self.pos += 1;
+                    }
+                    None if terminator.is_none() => {
+                        text.push(c);
+                        self.pos += 1;
+                    }
+                    None => return Err(ParseError::Incomplete),
+                },
+                '$' => match self.read_dollar()? {
+                    Some(part) => {
+                        if !text.is_empty() {
+                            parts.push(WordPart::Literal(std::mem::take(&mut text)));
+                        }
+                        parts.push(part);
+                    }
+                    None => {
+                        text.push(c);
+                        self.pos += 1;
+                    }
+                },
+                '`' => {
+                    self.pos += 1;
+                    if !text.is_empty() {
+                        parts.push(WordPart::Literal(std::mem::take(&mut text)));
+                    }
+                    parts.push(WordPart::CommandSubst(self.read_backquoted()?));
+                }
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(WordPart::Literal(text));
+        }
+
+        Ok(parts)
+    }
+
+    fn read_word(&mut self) -> Result<Word, ParseError> {
+        self.read_parts(Self::is_word_end)
+    }
+
+    fn read_parts(&mut self, is_end: impl Fn(char) -> bool) -> Result<Word, ParseError> {
+        let mut word = Word::default();
+        let mut literal = String::new();
+
+        while let Some(c) = self.peek() {
+            if is_end(c) && !self.at_process_subst() {
+                break;
+            }
+
+            let part = match c {
+                '"' => {
+                    self.pos += 1;
+                    WordPart::DoubleQuoted(self.read_double_quoted()?)
+                }
+                '\'' => {
+                    self.pos += 1;
+                    WordPart::SingleQuoted(self.read_single_quoted()?)
+                }
+                '\\' => match self.peek_at(1) {
+                    Some('\n') => {
+                        self.pos += 2;
+                        continue;
+                    }
+                    Some(escaped) => {
+                        self.pos += 2;
+                        WordPart::Escaped(escaped)
+                    }
+                    None => return Err(ParseError::Incomplete),
+                },
+                '$' if self.peek_at(1) == Some('\'') => {
+                    self.pos += 2;
+                    WordPart::SingleQuoted(self.read_ansi_c_quoted()?)
+                }
+                '$' => match self.read_dollar()? {
+                    Some(part) => part,
+                    None => {
+                        literal.push(c);
+                        self.pos += 1;
+                        continue;
+                    }
+                },
+                '`' => {
+                    self.pos += 1;
+                    WordPart::CommandSubst(self.read_backquoted()?)
+                }
+                '<' | '>' if self.at_process_subst() => {
+                    self.pos += 2;
+                    WordPart::ProcessSubst(c == '>', self.read_command_subst()?)
+                }
+                _ => {
+                    literal.push(c);
+                    self.pos += 1;
+                    continue;
+                }
+            };
+
+            if !literal.is_empty() {
+                word.parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+            }
+            word.parts.push(part);
+        }
+
+        if !literal.is_empty() {
+            word.parts.push(WordPart::Literal(literal));
+        }
+
+        Ok(word)
+    }
+
+    fn delimiter_text(parts: &[WordPart], text: &mut String) -> bool {
+        let mut quoted = false;
+
+        for part in parts {
+            match part {
+                WordPart::Literal(s) => text.push_str(s),
+                WordPart::SingleQuoted(s) => {
+                    text.push_str(s);
+                    quoted = true;
+                }
+                WordPart::Escaped(c) => {
+                    text.push(*c);
+                    quoted = true;
+                }
+                WordPart::DoubleQuoted(parts) => {
+                    Self::delimiter_text(parts, text);
+                    quoted = true;
+                }
+                WordPart::Param(_) | WordPart::CommandSubst(_) | WordPart::ProcessSubst(..) | WordPart::Arith(_) => text.push_str(&part.to_string()),
+            }
+        }
+
+        quoted
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        if self.pos >= self.chars.len() {
+            return None;
+        }
+
+        let mut line = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == '\n' {
+                break;
+            }
+            line.push(c);
+        }
+
+        Some(line)
+    }
+
+    fn read_heredoc_bodies(&mut self) -> Result<(), ParseError> {
+        for heredoc in std::mem::take(&mut self.heredocs) {
+            let mut body = String::new();
+
+            loop {
+                let mut line = match self.read_line() {
+                    Some(line) => line,
+                    None => return Err(ParseError::Incomplete),
+                };
+
+                if heredoc.strip_tabs {
+                    line = line.trim_start_matches('\t').to_string();
+                }
+                if line == heredoc.delimiter {
+                    break;
+                }
+
+                body += &line;
+                body.push('\n');
+            }
+
+            let parts = if heredoc.quoted {
+                vec![WordPart::SingleQuoted(body)]
+            } else {
+                vec![WordPart::DoubleQuoted(Lexer::new(&body).read_quoted_parts(None)?)]
+            };
+            self.tokens[heredoc.token] = Token::Word(Word { parts });
+        }
+
+        Ok(())
+    }
+
+    fn skip_comment(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn push_next_token(&mut self) -> Result<bool, ParseError> {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t') => self.pos += 1,
+                Some('\\') if self.peek_at(1) == Some('\n') => self.pos += 2,
+                Some('#') => self.skip_comment(),
+                Some(_) => break,
+                None => return Ok(false),
+            }
+        }
+
+        let operator = match self.at_process_subst() {
+            true => None,
+            false => self.read_io_number().or_else(|| self.read_arith()).or_else(|| self.read_operator()),
+        };
+        if let Some(token) = operator {
+            self.heredoc_op = match token {
+                Token::Redirect(_, RedirectOp::DLess) => Some(false),
+                Token::Redirect(_, RedirectOp::DLessDash) => Some(true),
+                _ => None,
+            };
+            let newline = token == Token::Operator(Operator::Newline);
+
+            self.tokens.push(token);
+            if newline {
+                self.read_heredoc_bodies()?;
+            }
+            return Ok(true);
+        }
+
+        let word = self.read_word()?;
+        if let Some(strip_tabs) = self.heredoc_op.take() {
+            let mut delimiter = String::new();
+            let quoted = Self::delimiter_text(&word.parts, &mut delimiter);
+
+            self.heredocs.push(PendingHereDoc { token: self.tokens.len(), delimiter, quoted, strip_tabs });
+        }
+        self.tokens.push(Token::Word(word));
+
+        Ok(true)
+    }
+
+    pub fn tokenize(mut self) -> Result<Vec<Token>, ParseError> {
+        while self.push_next_token()? {}
+
+        if !self.heredocs.is_empty() {
+            return Err(ParseError::Incomplete);
+        }
+
+        Ok(self.tokens)
+    }
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    Lexer::new(input).tokenize()
+}
+
+pub fn quoted_parts(input: &str) -> Result<Vec<WordPart>, ParseError> {
+    Lexer::new(input).read_quoted_parts(None)
+}
@@ -1,24 +1,55 @@
+use std::env;
 use std::io;
 use std::io::Write;
+use std::path::Path;
+use std::process::exit;
 use crossterm::cursor::MoveTo;
-use ctrlc;
 use parser::get_cursor_position;
 use crossterm::execute;
 
 mod executor;
 mod parser;
+mod lexer;
+mod ast;
+mod expand;
+mod shell;
+mod redirect;
+mod jobs;
+mod alias;
+mod functions;
+mod builtins;
+mod vars;
+mod dirs;
+mod arith;
+mod brace;
+mod script;
 mod history;
 mod autocompletion;
 
-fn main() {
-    ctrlc::set_handler(move || {
-        println!();
-    }).expect("Error setting Ctrl+C handler");
+fn source_startup_file(shell: &mut shell::Shell, path: &str) {
+    if let Err(err) = script::exec_file(shell, path) {
+        eprintln!("shsh: {}: {}", path, redirect::describe_error(&err));
+    }
+}
+
+fn run_interactive(mut shell: shell::Shell, rcfile: Option<String>) -> ! {
+    shell.interactive = true;
+    shell.terminal = jobs::Terminal::init();
+
+    for (name, value) in alias::DEFAULTS {
+        shell.aliases.insert(name.to_string(), value.to_string());
+    }
+
+    if let Some(rcfile) = rcfile {
+        source_startup_file(&mut shell, &rcfile);
+    }
 
     let mut completion = autocompletion::Completion::init();
     completion.get_cmds();
 
     loop {
+        shell.jobs.notify();
+
         let pos = get_cursor_position();
 
         execute!(std::io::stdout(), MoveTo(0, pos[1])).expect("Problem with moving cursor");
@@ -26,8 +57,122 @@ fn main() {
         io::stdout().flush().unwrap();
         execute!(std::io::stdout(), MoveTo(2, pos[1])).expect("Problem with moving cursor");
 
-        let command = parser::parse_input(&completion);
+        let list = parser::parse_input(&completion, &shell.aliases);
         println!();
-        executor::exec_command(command);
+        executor::exec_list(&mut shell, &list);
+        shell.flow = None;
+    }
+}
+
+fn main() {
+    let mut shell = shell::Shell::new();
+    dirs::init(&mut shell);
+    let mut args = env::args().peekable();
+
+    if let Some(name) = args.next() {
+        shell.name = name;
     }
+
+    let home = executor::get_env("HOME".to_string());
+    let mut rcfile = Some(format!("{}/.shshrc", home));
+    let mut profile = true;
+    let mut login = shell.name.starts_with('-');
+    let mut command = false;
+    let mut stdin = false;
+    let mut interactive = false;
+
+    while let Some(arg) = args.next_if(|arg| (arg.starts_with('-') || arg.starts_with('+')) && arg.len() > 1) {
+        match arg.as_str() {
+            "--" => break,
+            "--norc" => rcfile = None,
+            "--noprofile" => profile = false,
+            "--login" => login = true,
+            "--rcfile" => match args.next() {
+                Some(path) => rcfile = Some(path),
+                None => {
+                    eprintln!("shsh: --rcfile: option requires an argument");
+                    exit(2)
+                }
+            },
+            _ if arg.starts_with("--") => {
+                eprintln!("shsh: {}: invalid option", arg);
+                exit(2)
+            }
+            _ => {
+                let enable = arg.starts_with('-');
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'c' if enable => command = true,
+                        's' if enable => stdin = true,
+                        'i' if enable => interactive = true,
+                        'l' if enable => login = true,
+                        'o' => {
+                            let name = args.next().unwrap_or_default();
+                            match shell.options.get_mut(&name) {
+                                Some(option) => *option = enable,
+                                None => {
+                                    eprintln!("shsh: {}: invalid option name", name);
+                                    exit(2)
+                                }
+                            }
+                        }
+                        _ => match shell::Options::from_flag(flag).and_then(|name| shell.options.get_mut(name)) {
+                            Some(option) => *option = enable,
+                            None => {
+                                eprintln!("shsh: {}{}: invalid option", &arg[..1], flag);
+                                exit(2)
+                            }
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    if login && profile {
+        let path = format!("{}/.shsh_profile", home);
+        if Path::new(&path).exists() {
+            source_startup_file(&mut shell, &path);
+        }
+    }
+
+    if command {
+        let source = match args.next() {
+            Some(source) => source,
+            None => {
+                eprintln!("shsh: -c: option requires an argument");
+                exit(2)
+            }
+        };
+
+        if let Some(name) = args.next() {
+            shell.name = name;
+        }
+        shell.positional = args.collect();
+
+        exit(script::exec_source(&mut shell, &source));
+    }
+
+    if !stdin {
+        if let Some(path) = args.next() {
+            shell.name = path.clone();
+            shell.positional = args.collect();
+
+            match script::exec_file(&mut shell, &path) {
+                Ok(status) => exit(status),
+                Err(err) => {
+                    eprintln!("shsh: {}: {}", path, redirect::describe_error(&err));
+                    exit(127)
+                }
+            }
+        }
+    }
+
+    shell.positional = args.collect();
+
+    if interactive || unsafe { libc::isatty(0) } == 1 {
+        run_interactive(shell, rcfile.filter(|path| Path::new(path).exists()));
+    }
+
+    exit(script::exec_stdin(&mut shell));
 }
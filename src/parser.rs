@@ -1,6 +1,6 @@
+use std::collections::BTreeMap;
 use std::io::{self, Write};
 use std::process::exit;
-use std::collections::VecDeque;
 
 use crossterm::{
     event::{self, KeyCode, KeyEvent, KeyModifiers, Event},
@@ -8,27 +8,11 @@ use crossterm::{
     cursor::{MoveTo, MoveLeft, MoveRight, position},
     terminal::{ClearType, Clear, size, DisableLineWrap, EnableLineWrap},
 };
-use regex::{Captures, Regex};
 
 use crate::history;
-use crate::executor;
 use crate::autocompletion;
-
-pub struct Command {
-    pub name: String,
-    pub args: Vec<String>,
-    pub redirect_file: String,
-}
-
-impl Command {
-    pub fn new() -> Self {
-        let name = String::new();
-        let args: Vec<String> = vec![];
-        let redirect_file = String::new();
-
-        Self { name, args, redirect_file }
-    }
-}
+use crate::ast;
+use crate::lexer::ParseError;
 
 fn clear_input(begin_pos: [u16; 2], prompt: char) {
     execute!(std::io::stdout(), MoveTo(0, begin_pos[1])).expect("Problem with moving cursor");
@@ -38,7 +22,7 @@ fn clear_input(begin_pos: [u16; 2], prompt: char) {
     execute!(std::io::stdout(), MoveTo(2, begin_pos[1])).expect("Problem with moving cursor");
 }
 
-fn render_text(text: &String, mut cur_pos: [u16; 2], mut offset: usize, leave_the_cursor: bool) -> ([u16; 2], usize) {
+fn render_text(text: &str, mut cur_pos: [u16; 2], mut offset: usize, leave_the_cursor: bool) -> ([u16; 2], usize) {
     let (col, row) = size().unwrap();
 
     execute!(std::io::stdout(), MoveTo(cur_pos[0], cur_pos[1])).expect("Problem with moving cursor");
@@ -58,7 +42,7 @@ fn render_text(text: &String, mut cur_pos: [u16; 2], mut offset: usize, leave_th
         let pos = get_cursor_position();
 
         if row <= pos[1] + 1 && col - 1 == pos[0] && text.len() != end_index {
-            print!("\n");
+            println!();
             offset += 1;
             cur_pos[1] -= 1;
         } else {
@@ -96,35 +80,6 @@ pub fn get_cursor_position() -> [u16; 2] {
     [pos.0, pos.1]
 }
 
-fn split_user_input(input: &mut String) -> Vec<String> {
-    let mut splited_input: Vec<String> = vec![];
-    let mut word = String::new();
-
-    let mut d_qoutes_occured = false;
-    for c in input.chars() {
-        match c {
-            ' ' => {
-                if !d_qoutes_occured {
-                    if !word.is_empty() {
-                        splited_input.push(word.clone());
-                        word.clear();
-                    }
-                }else{
-                    word.push(' ');
-                }
-            }
-            '"' => d_qoutes_occured = !d_qoutes_occured,
-            _ => word.push(c),
-        }
-    }
-
-    if !word.is_empty() {
-        splited_input.push(word.clone());
-    }
-
-    splited_input
-}
-
 fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &autocompletion::Completion, prompt: char, mut offset: usize) -> String {
     let mut user_input = String::new();
 
@@ -150,7 +105,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
         match event {
             Event::Key(KeyEvent { code, modifiers, .. }) => {
                 if modifiers == KeyModifiers::CONTROL && code == KeyCode::Char('d') {
-                    render_text(&"exit\n".to_string(), begin_pos, offset, true);
+                    render_text("exit\n", begin_pos, offset, true);
 
                     crossterm::terminal::disable_raw_mode().expect("Problem with disabling raw mode");
                     execute!(std::io::stdout(), EnableLineWrap).expect("Problem with enabling line wrap");
@@ -188,7 +143,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                         let pos_x_moved = (offset * col as usize + pos[0] as usize) - 3;
                         user_input.remove(pos_x_moved);
 
-                        (pos, offset) = render_text(&user_input[pos_x_moved..].to_string(), [pos[0] - 1, pos[1]], offset, false);
+                        (pos, offset) = render_text(&user_input[pos_x_moved..], [pos[0] - 1, pos[1]], offset, false);
 
                         execute!(std::io::stdout(), MoveTo(pos[0], pos[1])).expect("Problem with moving cursor");
                     }
@@ -263,7 +218,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                     KeyCode::Tab => {
                         tab_counter += 1;
 
-                        if user_input == "" {
+                        if user_input.is_empty() {
                             continue
                         }
 
@@ -272,7 +227,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                                 let split: Vec<_>  = user_input.split_whitespace().collect();
 
                                 let mut path = ".".to_string();
-                                if split.len() > 1 && user_input.chars().last().unwrap() != ' ' {
+                                if split.len() > 1 && !user_input.ends_with(' ') {
                                     path = split.last().unwrap().to_string();
                                 }
 
@@ -292,7 +247,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                                 tab_cmd_complete.sort();
                             }
 
-                            if tab_cmd_complete.len() < 1 {
+                            if tab_cmd_complete.is_empty() {
                                 continue;
                             }
 
@@ -304,9 +259,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                             let cols = col.div_ceil((max_cmd_length+4) as u16) - 1;
                             let rows = tab_cmd_complete.len().div_ceil(cols as usize);
 
-                            over_term = if rows >= (row - pos[1]) as usize {
-                                true
-                            }else { false };
+                            over_term = rows >= (row - pos[1]) as usize;
                             
                             println!();
                             for (i, cmd) in tab_cmd_complete.iter().enumerate() {
@@ -336,7 +289,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                             io::stdout().flush().unwrap();
                         }
 
-                        if tab_cmd_complete.len() == 0 {
+                        if tab_cmd_complete.is_empty() {
                             continue;
                         }
 
@@ -359,7 +312,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                                 if is_path_completion {
                                     let split: Vec<_>  = user_input.split_whitespace().collect();
                                     if split.len() > 1 {
-                                        user_input = (&user_input[..completion_pos_x]).to_string() + " " + &tab_cmd_complete[tab_counter - 2];
+                                        user_input = user_input[..completion_pos_x].to_string() + " " + &tab_cmd_complete[tab_counter - 2];
                                     } else {
                                         user_input += &tab_cmd_complete[tab_counter - 2];
                                     }
@@ -379,7 +332,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                             if is_path_completion {
                                 let split: Vec<_>  = user_input.split_whitespace().collect();
                                 if split.len() > 1 {
-                                    user_input = (&user_input[..completion_pos_x]).to_string() + &tab_cmd_complete[tab_counter - 2];
+                                    user_input = user_input[..completion_pos_x].to_string() + &tab_cmd_complete[tab_counter - 2];
                                 } else {
                                     user_input += &tab_cmd_complete[tab_counter - 2];
                                 }
@@ -406,9 +359,9 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
 
                         pos = get_cursor_position();
                         if offset == 0 {
-                            (pos, offset) = render_text(&user_input[usize::from(pos[0] - 2)..].to_string(), pos, offset, false);
+                            (pos, offset) = render_text(&user_input[usize::from(pos[0] - 2)..], pos, offset, false);
                         }else {
-                            (pos, offset) = render_text(&user_input[usize::from(offset as u16 * col + pos[0]) - 2..].to_string(), pos, offset, false);
+                            (pos, offset) = render_text(&user_input[usize::from(offset as u16 * col + pos[0]) - 2..], pos, offset, false);
                         }
                     
                         execute!(std::io::stdout(), MoveTo(pos[0]+1, pos[1])).expect("Problem with moving cursor");
@@ -444,7 +397,7 @@ fn get_line(begin_pos: [u16; 2], history: &mut history::History, completion: &au
                 offset = 0;
 
                 let pos;
-                (pos, offset) = render_text(&user_input[usize::from(begin_pos[0] - 2)..].to_string(), begin_pos, offset, true);
+                (pos, offset) = render_text(&user_input[usize::from(begin_pos[0] - 2)..], begin_pos, offset, true);
                    
                 if user_input.len() >= usize::from(pos[0]) + (offset * usize::from(col)) && pos[0] + 1 == col {
                     if pos[1] + 1 >= row {
@@ -471,16 +424,16 @@ fn parse_multiline(mut begin_pos: [u16; 2], cmd_history: &mut history::History,
 
         if let Some(c) = user_input.chars().last() {
             if c != '\\' {
-                arg += &user_input.to_string();
+                arg += &user_input;
                 break
             }
-            arg += &(user_input[..user_input.len()-1].to_string());
+            arg += &user_input[..user_input.len()-1];
         }
     }
     arg
 }
 
-pub fn parse_input(completion: &autocompletion::Completion) -> VecDeque<Command> {
+pub fn parse_input(completion: &autocompletion::Completion, aliases: &BTreeMap<String, String>) -> ast::List {
     let mut cmd_history = history::init();
 
     let pos = get_cursor_position();
@@ -493,50 +446,29 @@ pub fn parse_input(completion: &autocompletion::Completion) -> VecDeque<Command>
 
     cmd_history.add_to_string(user_input.clone());
 
-    if let Some(c) = user_input.trim().chars().last() {
-        if c == '\\' {
+    let list = loop {
+        if user_input.ends_with('\\') {
             user_input.pop();
-            user_input += &parse_multiline(pos, &mut cmd_history, completion);
-        }
-    }
-
-    let cmd_vec: Vec<_> = user_input.split("|").collect();
-
-    let mut commands: VecDeque<Command> = Default::default();
-    for cmd in cmd_vec {
-        let mut command: Command = Command::new();
-
-        let command_splited = split_user_input(&mut (cmd.to_string()));
-
-        command.name = command_splited[0].clone();
-        command.args = command_splited[1..].to_vec();
-
-        let pattern = Regex::new("[$^][A-Za-z0-9]+").unwrap();
-
-        command.name = pattern.replace_all(&command.name, |c: &Captures| {
-            format!("{}", executor::get_env((&c[0])[1..].to_string()))
-        }).to_string();
-
-        for arg in &mut command.args {
-            *arg = pattern.replace_all(arg, |c: &Captures| {
-                format!("{}", executor::get_env((&c[0])[1..].to_string()))
-            }).to_string();
+            user_input += &parse_multiline(get_cursor_position(), &mut cmd_history, completion);
+            continue;
         }
 
-        if let Some(index) = command.args.iter().position(|v| v.contains('>')) {
-            if command.args[index].len() == 1 {
-                command.redirect_file = command.args[index..index+2].concat()[1..].to_string();
-                command.args.remove(index+1);
-            }else{
-                command.redirect_file = command.args[index][1..].to_string();
+        match ast::parse(&user_input, aliases) {
+            Ok(list) => break list,
+            Err(ParseError::Incomplete) => {
+                let line = parse_multiline(get_cursor_position(), &mut cmd_history, completion);
+                user_input.push('\n');
+                user_input += &line;
+            }
+            Err(err) => {
+                println!();
+                eprintln!("shsh: {}", err);
+                break Default::default();
             }
-            command.args.remove(index);
         }
-
-        commands.push_back(command);
-    }
+    };
 
     cmd_history.write_history();
 
-    commands
+    list
 }
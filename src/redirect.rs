@@ -0,0 +1,216 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Seek, Write};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::ast::Redirect;
+use crate::expand;
+use crate::lexer::RedirectOp;
+use crate::shell::Shell;
+
+const SAVED_FD_MIN: RawFd = 10;
+
+static ANONYMOUS_FILES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    unsafe { Ok((OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]))) }
+}
+
+pub fn anonymous_file(content: &str) -> io::Result<OwnedFd> {
+    let path = std::env::temp_dir().join(format!("shsh-{}-{}", std::process::id(), ANONYMOUS_FILES.fetch_add(1, Ordering::Relaxed)));
+
+    let mut file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+    fs::remove_file(&path)?;
+
+    file.write_all(content.as_bytes())?;
+    file.rewind()?;
+
+    Ok(OwnedFd::from(file))
+}
+
+pub struct SavedFds {
+    saved: Vec<(RawFd, Option<RawFd>)>,
+}
+
+impl SavedFds {
+    pub fn new() -> Self {
+        Self { saved: vec![] }
+    }
+
+    fn save(&mut self, fd: RawFd) {
+        if self.saved.iter().any(|(target, _)| *target == fd) {
+            return;
+        }
+
+        if fd == 1 {
+            let _ = io::stdout().flush();
+        }
+
+        let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, SAVED_FD_MIN) };
+        self.saved.push((fd, if copy == -1 { None } else { Some(copy) }));
+    }
+
+    pub fn redirect(&mut self, src: OwnedFd, target: RawFd) -> io::Result<()> {
+        if src.as_raw_fd() == target {
+            self.save(target);
+            unsafe { libc::fcntl(target, libc::F_SETFD, 0) };
+            let _ = src.into_raw_fd();
+            return Ok(());
+        }
+
+        self.duplicate(src.as_raw_fd(), target)
+    }
+
+    pub fn duplicate(&mut self, src: RawFd, target: RawFd) -> io::Result<()> {
+        self.save(target);
+
+        if unsafe { libc::dup2(src, target) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn close(&mut self, target: RawFd) {
+        self.save(target);
+        unsafe { libc::close(target) };
+    }
+
+    pub fn apply(&mut self, shell: &mut Shell, redirects: &[Redirect]) -> Result<(), String> {
+        for redirect in redirects {
+            self.apply_one(shell, redirect)?;
+        }
+        Ok(())
+    }
+
+    fn apply_one(&mut self, shell: &mut Shell, redirect: &Redirect) -> Result<(), String> {
+        let target = expand::expand_word(shell, &redirect.target)?;
+        let error = |err: io::Error| format!("{}: {}", target, describe_error(&err));
+
+        let default_fd = match redirect.op {
+            RedirectOp::Less | RedirectOp::LessGreat | RedirectOp::LessAnd => 0,
+            RedirectOp::TLess | RedirectOp::DLess | RedirectOp::DLessDash => 0,
+            _ => 1,
+        };
+        let fd = redirect.fd.unwrap_or(default_fd);
+
+        self.save(fd);
+        if matches!(redirect.op, RedirectOp::AndGreat | RedirectOp::AndDGreat) || redirect.op == RedirectOp::GreatAnd && redirect.fd.is_none() {
+            self.save(2);
+        }
+
+        match redirect.op {
+            RedirectOp::Less => {
+                let file = File::open(&target).map_err(error)?;
+                self.redirect(file.into(), fd).map_err(error)
+            }
+            RedirectOp::Great if shell.options.noclobber => {
+                let file = open_noclobber(&target).map_err(error)?;
+                self.redirect(file.into(), fd).map_err(error)
+            }
+            RedirectOp::Great | RedirectOp::Clobber => {
+                let file = File::create(&target).map_err(error)?;
+                self.redirect(file.into(), fd).map_err(error)
+            }
+            RedirectOp::DGreat => {
+                let file = OpenOptions::new().append(true).create(true).open(&target).map_err(error)?;
+                self.redirect(file.into(), fd).map_err(error)
+            }
+            RedirectOp::LessGreat => {
+                let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&target).map_err(error)?;
+                self.redirect(file.into(), fd).map_err(error)
+            }
+            RedirectOp::LessAnd | RedirectOp::GreatAnd => {
+                if target == "-" {
+                    self.close(fd);
+                    return Ok(());
+                }
+
+                match target.parse::<RawFd>() {
+                    Ok(src) => self.duplicate(src, fd).map_err(|err| format!("{}: {}", src, describe_error(&err))),
+                    Err(_) if redirect.op == RedirectOp::GreatAnd && redirect.fd.is_none() => {
+                        let file = File::create(&target).map_err(error)?;
+                        self.redirect(file.into(), 1).map_err(error)?;
+                        self.duplicate(1, 2).map_err(error)
+                    }
+                    Err(_) => Err(format!("{}: ambiguous redirect", target)),
+                }
+            }
+            RedirectOp::AndGreat | RedirectOp::AndDGreat => {
+                let file = if redirect.op == RedirectOp::AndGreat {
+                    File::create(&target)
+                } else {
+                    OpenOptions::new().append(true).create(true).open(&target)
+                }.map_err(error)?;
+
+                self.redirect(file.into(), 1).map_err(error)?;
+                self.duplicate(1, 2).map_err(error)
+            }
+            RedirectOp::TLess => {
+                let file = anonymous_file(&(target.clone() + "\n")).map_err(error)?;
+                self.redirect(file, fd).map_err(error)
+            }
+            RedirectOp::DLess | RedirectOp::DLessDash => {
+                let file = anonymous_file(&target).map_err(|err| format!("here-document: {}", describe_error(&err)))?;
+                self.redirect(file, fd).map_err(|err| format!("here-document: {}", describe_error(&err)))
+            }
+        }
+    }
+
+    pub fn saved_fds(&self) -> Vec<RawFd> {
+        self.saved.iter().filter_map(|(_, copy)| *copy).collect()
+    }
+
+    pub fn keep(self) {
+        for copy in self.saved.into_iter().filter_map(|(_, copy)| copy) {
+            unsafe { libc::close(copy) };
+        }
+    }
+
+    pub fn restore(self) {
+        let _ = io::stdout().flush();
+
+        for (fd, copy) in self.saved.into_iter().rev() {
+            match copy {
+                Some(copy) => unsafe {
+                    libc::dup2(copy, fd);
+                    libc::close(copy);
+                },
+                None => unsafe {
+                    libc::close(fd);
+                },
+            }
+        }
+    }
+}
+
+fn open_noclobber(target: &str) -> io::Result<File> {
+    match OpenOptions::new().write(true).create_new(true).open(target) {
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+            if Path::new(target).is_file() {
+                return Err(io::Error::new(ErrorKind::AlreadyExists, "cannot overwrite existing file"));
+            }
+            OpenOptions::new().write(true).open(target)
+        }
+        result => result,
+    }
+}
+
+pub fn describe_error(err: &io::Error) -> String {
+    match err.kind() {
+        ErrorKind::NotFound => "No such file or directory".to_string(),
+        ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        _ => match err.raw_os_error() {
+            Some(libc::EBADF) => "Bad file descriptor".to_string(),
+            Some(libc::EISDIR) => "Is a directory".to_string(),
+            Some(libc::ENOTDIR) => "Not a directory".to_string(),
+            _ => err.to_string(),
+        },
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+
+use crate::ast;
+use crate::builtins::Stdio;
+use crate::executor;
+use crate::lexer::ParseError;
+use crate::redirect;
+use crate::shell::{Flow, Shell};
+
+fn read_stdin_line() -> Option<String> {
+    let mut bytes = vec![];
+
+    loop {
+        let mut byte = 0u8;
+        match unsafe { libc::read(0, &mut byte as *mut u8 as *mut libc::c_void, 1) } {
+            1 => {
+                bytes.push(byte);
+                if byte == b'\n' {
+                    break;
+                }
+            }
+            -1 if io::Error::last_os_error().kind() == ErrorKind::Interrupted => continue,
+            _ => break,
+        }
+    }
+
+    if bytes.is_empty() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn ends_with_continuation(buffer: &str) -> bool {
+    let line = buffer.strip_suffix('\n').unwrap_or(buffer);
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+fn exec_lines(shell: &mut Shell, name: &str, mut next_line: impl FnMut() -> Option<String>) -> i32 {
+    let mut buffer = String::new();
+    let mut line = 0;
+    let mut status = 0;
+
+    while shell.flow.is_none() {
+        let text = match next_line() {
+            Some(text) => text,
+            None => break,
+        };
+
+        line += 1;
+        buffer += &text;
+        if !buffer.ends_with('\n') {
+            buffer.push('\n');
+        }
+
+        if ends_with_continuation(&buffer) {
+            continue;
+        }
+
+        match ast::parse(&buffer, &shell.aliases) {
+            Ok(list) => {
+                buffer.clear();
+                status = executor::exec_list(shell, &list);
+            }
+            Err(ParseError::Incomplete) => (),
+            Err(err) => {
+                eprintln!("{}: line {}: {}", name, line, err);
+                return 2
+            }
+        }
+    }
+
+    if !buffer.trim().is_empty() {
+        eprintln!("{}: line {}: {}", name, line, ParseError::Incomplete);
+        return 2
+    }
+
+    status
+}
+
+fn exec_named_source(shell: &mut Shell, name: &str, source: &str) -> i32 {
+    let mut lines = source.split_inclusive('\n').map(|line| line.to_string());
+    exec_lines(shell, name, || lines.next())
+}
+
+pub fn exec_source(shell: &mut Shell, source: &str) -> i32 {
+    let name = shell.name.clone();
+    exec_named_source(shell, &name, source)
+}
+
+pub fn exec_file(shell: &mut Shell, path: &str) -> io::Result<i32> {
+    let source = fs::read(path)?;
+    Ok(exec_named_source(shell, path, &String::from_utf8_lossy(&source)))
+}
+
+pub fn exec_stdin(shell: &mut Shell) -> i32 {
+    let name = shell.name.clone();
+    exec_lines(shell, &name, read_stdin_line)
+}
+
+fn find_source(shell: &Shell, name: &str) -> String {
+    if name.contains('/') {
+        return name.to_string();
+    }
+
+    shell
+        .get_var("PATH")
+        .unwrap_or_default()
+        .split(':')
+        .map(|dir| Path::new(dir).join(name))
+        .find(|path| path.is_file())
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| name.to_string())
+}
+
+pub fn source(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let name = match args.first() {
+        Some(name) => name,
+        None => {
+            let _ = writeln!(stdio.stderr, "shsh: source: filename argument required");
+            return 2
+        }
+    };
+
+    let positional = (args.len() > 1).then(|| std::mem::replace(&mut shell.positional, args[1..].to_vec()));
+
+    shell.source_depth += 1;
+    let status = match exec_file(shell, &find_source(shell, name)) {
+        Ok(status) => status,
+        Err(err) => {
+            let _ = writeln!(stdio.stderr, "shsh: {}: {}", name, redirect::describe_error(&err));
+            1
+        }
+    };
+    shell.source_depth -= 1;
+
+    if shell.flow == Some(Flow::Return) {
+        shell.flow = None;
+    }
+
+    if let Some(positional) = positional {
+        shell.positional = positional;
+    }
+
+    status
+}
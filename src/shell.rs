@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, HashMap};
+use std::os::fd::OwnedFd;
+use std::process;
+use std::rc::Rc;
+
+use crate::ast;
+use crate::builtins::Registry;
+use crate::jobs::{JobTable, Terminal};
+use crate::vars::{Variable, Variables};
+
+#[derive(Default)]
+pub struct Options {
+    pub errexit: bool,
+    pub noclobber: bool,
+    pub nounset: bool,
+    pub pipefail: bool,
+    pub xtrace: bool,
+}
+
+impl Options {
+    pub const NAMES: [&'static str; 5] = ["errexit", "noclobber", "nounset", "pipefail", "xtrace"];
+
+    pub fn from_flag(flag: char) -> Option<&'static str> {
+        match flag {
+            'e' => Some("errexit"),
+            'C' => Some("noclobber"),
+            'u' => Some("nounset"),
+            'x' => Some("xtrace"),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "errexit" => Some(&mut self.errexit),
+            "noclobber" => Some(&mut self.noclobber),
+            "nounset" => Some(&mut self.nounset),
+            "pipefail" => Some(&mut self.pipefail),
+            "xtrace" => Some(&mut self.xtrace),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "errexit" => Some(self.errexit),
+            "noclobber" => Some(self.noclobber),
+            "nounset" => Some(self.nounset),
+            "pipefail" => Some(self.pipefail),
+            "xtrace" => Some(self.xtrace),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Shopt {
+    pub dotglob: bool,
+    pub failglob: bool,
+    pub globstar: bool,
+    pub nullglob: bool,
+}
+
+impl Shopt {
+    pub const NAMES: [&'static str; 4] = ["dotglob", "failglob", "globstar", "nullglob"];
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "dotglob" => Some(&mut self.dotglob),
+            "failglob" => Some(&mut self.failglob),
+            "globstar" => Some(&mut self.globstar),
+            "nullglob" => Some(&mut self.nullglob),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "dotglob" => Some(self.dotglob),
+            "failglob" => Some(self.failglob),
+            "globstar" => Some(self.globstar),
+            "nullglob" => Some(self.nullglob),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    Return,
+    Break(usize),
+    Continue(usize),
+    Interrupt,
+}
+
+pub struct Shell {
+    pub last_status: i32,
+    pub pipestatus: Vec<i32>,
+    pub subst_status: Option<i32>,
+    pub options: Options,
+    pub shopt: Shopt,
+    pub interactive: bool,
+    pub jobs: JobTable,
+    pub terminal: Option<Terminal>,
+    pub last_background: Option<i32>,
+    pub proc_substs: Vec<(OwnedFd, i32)>,
+    pub subst_children: Vec<i32>,
+    pub name: String,
+    pub positional: Vec<String>,
+    pub vars: Variables,
+    pub aliases: BTreeMap<String, String>,
+    pub functions: HashMap<String, Rc<ast::List>>,
+    pub builtins: Registry,
+    pub locals: Vec<Vec<(String, Option<Variable>)>>,
+    pub dirstack: Vec<String>,
+    pub flow: Option<Flow>,
+    pub keep_redirects: bool,
+    pub source_depth: usize,
+    pub loop_depth: usize,
+    pub condition_depth: usize,
+    pid: u32,
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        Self {
+            last_status: 0,
+            pipestatus: vec![],
+            subst_status: None,
+            options: Options::default(),
+            shopt: Shopt::default(),
+            interactive: false,
+            jobs: JobTable::new(),
+            terminal: None,
+            last_background: None,
+            proc_substs: vec![],
+            subst_children: vec![],
+            name: "shsh".to_string(),
+            positional: vec![],
+            vars: Variables::from_env(),
+            aliases: BTreeMap::new(),
+            functions: HashMap::new(),
+            builtins: Registry::new(),
+            locals: vec![],
+            dirstack: vec![],
+            flow: None,
+            keep_redirects: false,
+            source_depth: 0,
+            loop_depth: 0,
+            condition_depth: 0,
+            pid: process::id(),
+        }
+    }
+
+    pub fn get_var(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.last_status.to_string()),
+            "!" => self.last_background.map(|pid| pid.to_string()),
+            "PIPESTATUS" => self.pipestatus.first().map(|status| status.to_string()),
+            "$" => Some(self.pid.to_string()),
+            "#" => Some(self.positional.len().to_string()),
+            "@" => Some(self.positional.join(" ")),
+            "*" => Some(self.positional.join(&self.ifs().chars().take(1).collect::<String>())),
+            "0" => Some(self.name.clone()),
+            _ if name.chars().all(|c| c.is_ascii_digit()) => {
+                name.parse::<usize>().ok().and_then(|n| self.positional.get(n.checked_sub(1)?).cloned())
+            }
+            _ => self.vars.get(name).map(|value| value.to_string()),
+        }
+    }
+
+    pub fn ifs(&self) -> String {
+        self.vars.get("IFS").unwrap_or(" \t\n").to_string()
+    }
+
+    pub fn set_var(&mut self, name: &str, value: &str) -> Result<(), String> {
+        self.vars.set(name, value)
+    }
+
+    pub fn get_array(&self, name: &str) -> Option<Vec<String>> {
+        match name {
+            "PIPESTATUS" => Some(self.pipestatus.iter().map(|status| status.to_string()).collect()),
+            _ => self.get_var(name).map(|value| vec![value]),
+        }
+    }
+
+    pub fn set_status(&mut self, pipestatus: Vec<i32>) {
+        self.last_status = if self.options.pipefail {
+            pipestatus.iter().rev().find(|status| **status != 0).copied().unwrap_or(0)
+        } else {
+            pipestatus.last().copied().unwrap_or(0)
+        };
+        self.pipestatus = pipestatus;
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
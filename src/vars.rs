@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::Write;
+
+use crate::builtins::Stdio;
+use crate::shell::{self, Shell};
+
+#[derive(Debug, Clone, Default)]
+pub struct Variable {
+    pub value: Option<String>,
+    pub exported: bool,
+    pub readonly: bool,
+}
+
+impl Variable {
+    fn flags(&self) -> String {
+        let mut flags = String::from("-");
+        if self.readonly {
+            flags.push('r');
+        }
+        if self.exported {
+            flags.push('x');
+        }
+        if flags.len() == 1 {
+            flags.push('-');
+        }
+        flags
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    vars: HashMap<String, Variable>,
+}
+
+impl Variables {
+    pub fn from_env() -> Self {
+        let vars = env::vars()
+            .map(|(name, value)| (name, Variable { value: Some(value), exported: true, readonly: false }))
+            .collect();
+
+        Self { vars }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).and_then(|var| var.value.as_deref())
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Variable> {
+        self.vars.get(name)
+    }
+
+    pub fn declare(&mut self, name: &str) -> &mut Variable {
+        self.vars.entry(name.to_string()).or_default()
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let var = self.declare(name);
+        if var.readonly {
+            return Err(format!("{}: readonly variable", name));
+        }
+
+        var.value = Some(value.to_string());
+        Ok(())
+    }
+
+    pub fn unset(&mut self, name: &str) -> Result<(), String> {
+        if self.vars.get(name).is_some_and(|var| var.readonly) {
+            return Err(format!("{}: cannot unset: readonly variable", name));
+        }
+
+        self.vars.remove(name);
+        Ok(())
+    }
+
+    pub fn restore(&mut self, name: &str, var: Option<Variable>) {
+        match var {
+            Some(var) => self.vars.insert(name.to_string(), var),
+            None => self.vars.remove(name),
+        };
+    }
+
+    pub fn exported(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .filter_map(|(name, var)| Some((name.as_str(), var.value.as_deref()?)))
+    }
+
+    fn sorted(&self) -> Vec<(&String, &Variable)> {
+        let mut vars: Vec<_> = self.vars.iter().collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        vars
+    }
+}
+
+fn quote_double(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        if "\"\\$`".contains(c) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn quote_single(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(|c| c.is_alphanumeric() || "-_./=:,+@%^".contains(c)) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn format_declare(name: &str, var: &Variable) -> String {
+    match &var.value {
+        Some(value) => format!("declare {} {}={}", var.flags(), name, quote_double(value)),
+        None => format!("declare {} {}", var.flags(), name),
+    }
+}
+
+fn print_declared(shell: &Shell, stdio: &mut Stdio, filter: impl Fn(&Variable) -> bool) {
+    for (name, var) in shell.vars.sorted() {
+        if filter(var) {
+            let _ = writeln!(stdio.stdout, "{}", format_declare(name, var));
+        }
+    }
+}
+
+pub fn print_all(shell: &Shell, stdio: &mut Stdio) {
+    for (name, var) in shell.vars.sorted() {
+        if let Some(value) = &var.value {
+            let _ = writeln!(stdio.stdout, "{}={}", name, quote_single(value));
+        }
+    }
+}
+
+fn localize(shell: &mut Shell, name: &str) -> Result<(), String> {
+    let frame = match shell.locals.last_mut() {
+        Some(frame) => frame,
+        None => return Ok(()),
+    };
+
+    if frame.iter().any(|(saved, _)| saved == name) {
+        return Ok(());
+    }
+
+    let saved = shell.vars.lookup(name).cloned();
+    if saved.as_ref().is_some_and(|var| var.readonly) {
+        return Err(format!("{}: readonly variable", name));
+    }
+
+    frame.push((name.to_string(), saved));
+    shell.vars.restore(name, Some(Variable::default()));
+    Ok(())
+}
+
+fn declare_names(shell: &mut Shell, stdio: &mut Stdio, builtin: &str, names: &[&String], local: bool, update: impl Fn(&mut Variable)) -> i32 {
+    let mut status = 0;
+
+    for arg in names {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+
+        if !shell::is_valid_name(name) {
+            let _ = writeln!(stdio.stderr, "shsh: {}: `{}': not a valid identifier", builtin, arg);
+            status = 1;
+            continue;
+        }
+
+        let result = match local {
+            true => localize(shell, name),
+            false => Ok(()),
+        };
+        let result = result.and_then(|_| match value {
+            Some(value) => shell.vars.set(name, value),
+            None => Ok(()),
+        });
+
+        if let Err(err) = result {
+            let _ = writeln!(stdio.stderr, "shsh: {}: {}", builtin, err);
+            status = 1;
+            continue;
+        }
+
+        update(shell.vars.declare(name));
+    }
+
+    status
+}
+
+pub fn export(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut unexport = false;
+    let mut names = vec![];
+
+    for arg in args {
+        match arg.as_str() {
+            "-n" if names.is_empty() => unexport = true,
+            "-p" if names.is_empty() => (),
+            _ if arg.starts_with('-') && names.is_empty() => {
+                let _ = writeln!(stdio.stderr, "shsh: export: {}: invalid option", arg);
+                let _ = writeln!(stdio.stderr, "export: usage: export [-n] [-p] [name[=value] ...]");
+                return 2
+            }
+            _ => names.push(arg),
+        }
+    }
+
+    if names.is_empty() {
+        print_declared(shell, stdio, |var| var.exported);
+        return 0
+    }
+
+    declare_names(shell, stdio, "export", &names, false, |var| var.exported = !unexport)
+}
+
+pub fn readonly(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let names: Vec<_> = args.iter().filter(|arg| *arg != "-p").collect();
+
+    if names.is_empty() {
+        print_declared(shell, stdio, |var| var.readonly);
+        return 0
+    }
+
+    declare_names(shell, stdio, "readonly", &names, false, |var| var.readonly = true)
+}
+
+pub fn unset(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut functions = false;
+    let mut variables = false;
+    let mut status = 0;
+
+    for arg in args {
+        match arg.as_str() {
+            "-f" => functions = true,
+            "-v" => variables = true,
+            _ if functions => {
+                shell.functions.remove(arg);
+            }
+            _ if !shell::is_valid_name(arg) => {
+                let _ = writeln!(stdio.stderr, "shsh: unset: `{}': not a valid identifier", arg);
+                status = 1;
+            }
+            _ if shell.vars.lookup(arg).is_some() => {
+                if let Err(err) = shell.vars.unset(arg) {
+                    let _ = writeln!(stdio.stderr, "shsh: unset: {}", err);
+                    status = 1;
+                }
+            }
+            _ if !variables => {
+                shell.functions.remove(arg);
+            }
+            _ => (),
+        }
+    }
+
+    status
+}
+
+pub fn declare(shell: &mut Shell, name: &str, args: &[String], stdio: &mut Stdio) -> i32 {
+    let mut export = None;
+    let mut readonly = false;
+    let mut print = false;
+    let mut global = false;
+    let mut functions = None;
+    let mut names = vec![];
+
+    for arg in args {
+        let enable = arg.starts_with('-');
+        if !names.is_empty() || !(enable || arg.starts_with('+')) || arg.len() < 2 {
+            names.push(arg);
+            continue;
+        }
+
+        for flag in arg[1..].chars() {
+            match flag {
+                'x' => export = Some(enable),
+                'r' if enable => readonly = true,
+                'p' => print = true,
+                'g' => global = true,
+                'f' => functions = Some(true),
+                'F' => functions = Some(false),
+                _ => {
+                    let _ = writeln!(stdio.stderr, "shsh: {}: {}{}: invalid option", name, &arg[..1], flag);
+                    let _ = writeln!(stdio.stderr, "{}: usage: {} [-fFgprx] [name[=value] ...]", name, name);
+                    return 2
+                }
+            }
+        }
+    }
+
+    if let Some(bodies) = functions {
+        let mut declared: Vec<_> = shell.functions.iter().collect();
+        declared.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (function, body) in declared {
+            if !names.is_empty() && !names.contains(&function) {
+                continue;
+            }
+            let _ = match bodies {
+                true => writeln!(stdio.stdout, "{} () {{\n{}\n}}", function, body),
+                false => writeln!(stdio.stdout, "declare -f {}", function),
+            };
+        }
+        return 0
+    }
+
+    if names.is_empty() {
+        print_declared(shell, stdio, |var| {
+            export.is_none_or(|export| var.exported == export) && (!readonly || var.readonly)
+        });
+        return 0
+    }
+
+    if print {
+        let mut status = 0;
+        for name in names {
+            match shell.vars.lookup(name) {
+                Some(var) => {
+                    let _ = writeln!(stdio.stdout, "{}", format_declare(name, var));
+                }
+                None => {
+                    let _ = writeln!(stdio.stderr, "shsh: declare: {}: not found", name);
+                    status = 1;
+                }
+            }
+        }
+        return status
+    }
+
+    let local = !global && !shell.locals.is_empty();
+    declare_names(shell, stdio, name, &names, local, |var| {
+        if let Some(export) = export {
+            var.exported = export;
+        }
+        var.readonly |= readonly;
+    })
+}
+
+pub fn local(shell: &mut Shell, args: &[String], stdio: &mut Stdio) -> i32 {
+    if shell.locals.is_empty() {
+        let _ = writeln!(stdio.stderr, "shsh: local: can only be used in a function");
+        return 1
+    }
+
+    let names: Vec<_> = args.iter().collect();
+    declare_names(shell, stdio, "local", &names, true, |_| ())
+}